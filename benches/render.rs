@@ -0,0 +1,84 @@
+#[macro_use]
+extern crate criterion;
+extern crate cubeglobe;
+
+use criterion::{BenchmarkId, Criterion};
+
+use cubeglobe::map::generator::{Generator, TerGenTwo};
+use cubeglobe::map::IsoMap;
+use cubeglobe::renderer::Renderer;
+
+/// Make a seeded renderer using the full tile set
+fn renderer<'a>() -> Renderer<'a> {
+    let config_toml = include_str!("../assets/full-tiles.toml");
+
+    let rconfig: Renderer = config_toml.parse().unwrap();
+    rconfig.with_seed(0)
+}
+
+/// Generate a `len`³ map with some soil and water on it
+fn generate(len: usize) -> IsoMap {
+    TerGenTwo::new()
+        .set_len(len)
+        .set_min_soil_cutoff(len / 2)
+        .set_max_water_level(len / 4)
+        .set_seed(0)
+        .generate()
+}
+
+/// Compare rendering small maps with `render_map`, which makes a new surface
+/// every time, and with a render context, which reuses one
+fn render_context(c: &mut Criterion) {
+    let rconfig = renderer();
+    let mut group = c.benchmark_group("render_context");
+
+    for &len in &[8, 16, 32, 64] {
+        let iso_map = generate(len);
+
+        group.bench_with_input(BenchmarkId::new("render_map", len), &iso_map, |b, iso_map| {
+            b.iter(|| rconfig.render_map(iso_map).unwrap())
+        });
+
+        let mut context = rconfig.render_context();
+        group.bench_with_input(
+            BenchmarkId::new("render_context", len),
+            &iso_map,
+            |b, iso_map| {
+                b.iter(|| {
+                    context.render(iso_map).unwrap();
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Compare the serial and the parallel renderer on the same map
+#[cfg(feature = "rayon")]
+fn render_parallel(c: &mut Criterion) {
+    let rconfig = renderer();
+    let iso_map = TerGenTwo::new()
+        .set_len(128)
+        .set_frequency(0.01)
+        .set_seed(0)
+        .generate();
+
+    let mut group = c.benchmark_group("render_parallel");
+    group.sample_size(10);
+
+    group.bench_function("render_map", |b| {
+        b.iter(|| rconfig.render_map(&iso_map).unwrap())
+    });
+    group.bench_function("render_map_parallel", |b| {
+        b.iter(|| rconfig.render_map_parallel(&iso_map).unwrap())
+    });
+
+    group.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
+criterion_group!(benches, render_context);
+#[cfg(feature = "rayon")]
+criterion_group!(benches, render_context, render_parallel);
+criterion_main!(benches);
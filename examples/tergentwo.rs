@@ -7,7 +7,7 @@ use cubeglobe::map::generator::{Generator, TerGenTwo};
 pub fn main() {
     let config_toml = include_str!("../assets/full-tiles.toml");
 
-    let rconfig = Renderer::from_config_str(config_toml).unwrap();
+    let rconfig: Renderer = config_toml.parse().unwrap();
 
     let iso_map = TerGenTwo::new()
         .set_len(32)
@@ -15,6 +15,7 @@ pub fn main() {
         .set_layer_height(7)
         .set_max_water_level(15)
         .set_min_soil_cutoff(30)
+        .set_beach_width(2)
         .generate();
 
     rconfig.render_map(&iso_map).unwrap().save_bmp("out.bmp").unwrap();
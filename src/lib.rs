@@ -1,17 +1,23 @@
 #![cfg_attr(feature = "cargo-clippy", allow(deref_addrof))]
 
+extern crate color_quant;
 extern crate failure;
 extern crate failure_derive;
 #[macro_use]
 extern crate ndarray;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate gif;
 extern crate sdl2;
 extern crate toml;
-#[macro_use]
 extern crate enum_iterator;
 extern crate noise;
 extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(test)]
+extern crate serde_json;
 
 pub mod map;
 pub mod renderer;
@@ -0,0 +1,91 @@
+//! Rendering maps as text, for tests and quick debugging in a terminal
+//!
+//! Every block is shown as a single character: `#` for rock, `"` for grass,
+//! `%` for soil, `~` for water, `.` for sand, `*` for snow, `?` for custom
+//! blocks, and a space for air. Each line of the output is one row along the
+//! x axis, with the first line at y = 0, and every line ends with a newline.
+//!
+//! ## Example use
+//! ```
+//! use cubeglobe::map::ascii;
+//! use cubeglobe::map::generator::{Generator, TerGenOne};
+//!
+//! let iso_map = TerGenOne::new().set_len(16).generate();
+//! println!("{}", ascii::render_top_down(&iso_map));
+//! ```
+
+use map::{Block, IsoMap};
+
+/// Render the map as seen from above, showing the topmost block that isn't air
+/// in every column
+pub fn render_top_down(isomap: &IsoMap) -> String {
+    render_with(isomap, |x, y| {
+        (0..isomap.dim_z())
+            .rev()
+            .map(|z| isomap.0[[x, y, z]])
+            .find(|&block| block != Block::Air)
+            .unwrap_or(Block::Air)
+    })
+}
+
+/// Render a single horizontal slice of the map, at height `z`
+///
+/// A slice outside of the map is rendered as all air.
+pub fn render_slice(isomap: &IsoMap, z: usize) -> String {
+    render_with(isomap, |x, y| isomap.get(x, y, z).unwrap_or(Block::Air))
+}
+
+/// Render every column of the map as the block `pick` returns for it
+fn render_with<F: Fn(usize, usize) -> Block>(isomap: &IsoMap, pick: F) -> String {
+    let mut out = String::with_capacity((isomap.dim_x() + 1) * isomap.dim_y());
+
+    for y in 0..isomap.dim_y() {
+        out.extend((0..isomap.dim_x()).map(|x| block_char(pick(x, y))));
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Get the character a block is shown as
+fn block_char(block: Block) -> char {
+    match block {
+        Block::Air => ' ',
+        Block::Rock => '#',
+        Block::Grass => '"',
+        Block::Soil => '%',
+        Block::Water => '~',
+        Block::Sand => '.',
+        Block::Snow => '*',
+        Block::Custom(_) => '?',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_map() -> IsoMap {
+        let mut iso_map = IsoMap::new_empty_dims(3, 2, 3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[1, 0, 1]] = Block::Soil;
+        iso_map.0[[1, 0, 2]] = Block::Grass;
+        iso_map.0[[2, 1, 1]] = Block::Water;
+        iso_map.0[[0, 1, 0]] = Block::Air;
+        iso_map
+    }
+
+    #[test]
+    fn top_down() {
+        assert_eq!(render_top_down(&sample_map()), "#\"#\n #~\n");
+    }
+
+    #[test]
+    fn slices() {
+        let iso_map = sample_map();
+
+        assert_eq!(render_slice(&iso_map, 0), "###\n ##\n");
+        assert_eq!(render_slice(&iso_map, 1), " % \n  ~\n");
+        assert_eq!(render_slice(&iso_map, 5), "   \n   \n");
+    }
+}
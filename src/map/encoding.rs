@@ -0,0 +1,367 @@
+//! Saving maps to and loading them from a compact binary format
+//!
+//! The format starts with the magic bytes `CUBE` and a version byte, followed
+//! by the lengths of the map along the x, y and z axes as little endian `u32`s.
+//! After that comes one byte per block, in x, y, z order, with z changing the
+//! fastest.
+//!
+//! Maps with [custom blocks](../enum.Block.html#variant.Custom) are written as
+//! version 2 instead, which is the same except that every block takes two
+//! bytes, as a little endian `u16`. Custom blocks are 256 plus their id, and
+//! named blocks have the same values as in version 1.
+//!
+//! Maps can also be saved in any format serde supports, since `IsoMap`
+//! implements `Serialize` and `Deserialize`. With serde, a map is a struct
+//! with its `shape`, the lengths along the x, y and z axes, and its `blocks`,
+//! in the same order as in the binary format.
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use failure::{Backtrace, Context, Fail};
+use ndarray::Array3;
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use map::{Block, IsoMap};
+
+/// Bytes every encoded map starts with
+const MAGIC: &[u8; 4] = b"CUBE";
+
+/// Version of the format written by [`IsoMap::to_bytes`](struct.IsoMap.html#method.to_bytes)
+const VERSION: u8 = 1;
+
+/// Version of the format with two bytes per block, written for maps with
+/// custom blocks
+const VERSION_WIDE: u8 = 2;
+
+/// Length of everything before the blocks
+const HEADER_LEN: usize = 4 + 1 + 3 * 4;
+
+impl IsoMap {
+    /// Encode the map in the binary format described in the
+    /// [`encoding`](encoding/index.html) module
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Only custom blocks have codes which don't fit in a byte
+        let wide = self.0.iter().any(|&block| block_to_code(block) > 255);
+        let block_len = if wide { 2 } else { 1 };
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.0.len() * block_len);
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(if wide { VERSION_WIDE } else { VERSION });
+        for &len in &[self.dim_x(), self.dim_y(), self.dim_z()] {
+            bytes.extend_from_slice(&u32_to_bytes(len as u32));
+        }
+
+        for &block in self.0.iter() {
+            let code = block_to_code(block);
+
+            bytes.push(code as u8);
+            if wide {
+                bytes.push((code >> 8) as u8);
+            }
+        }
+
+        bytes
+    }
+
+    /// Decode a map encoded with [`to_bytes`](#method.to_bytes)
+    pub fn from_bytes(bytes: &[u8]) -> Result<IsoMap, MapDecodeError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(MapDecodeErrorKind::BadMagic.into());
+        }
+
+        if bytes.len() < HEADER_LEN {
+            return Err(MapDecodeErrorKind::LengthMismatch {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            }.into());
+        }
+
+        let block_len = match bytes[4] {
+            VERSION => 1,
+            VERSION_WIDE => 2,
+            version => return Err(MapDecodeErrorKind::VersionMismatch(version).into()),
+        };
+
+        let len_x = u32_from_bytes(&bytes[5..9]) as usize;
+        let len_y = u32_from_bytes(&bytes[9..13]) as usize;
+        let len_z = u32_from_bytes(&bytes[13..17]) as usize;
+
+        let blocks = &bytes[HEADER_LEN..];
+        let expected = len_x
+            .checked_mul(len_y)
+            .and_then(|len| len.checked_mul(len_z))
+            .and_then(|len| len.checked_mul(block_len));
+
+        if expected != Some(blocks.len()) {
+            return Err(MapDecodeErrorKind::LengthMismatch {
+                expected: expected.map_or(usize::MAX, |len| len + HEADER_LEN),
+                actual: bytes.len(),
+            }.into());
+        }
+
+        let blocks = blocks
+            .chunks(block_len)
+            .map(|bytes| {
+                let code = bytes
+                    .iter()
+                    .rev()
+                    .fold(0, |code, &byte| (code << 8) | u16::from(byte));
+
+                block_from_code(code)
+            }).collect::<Result<Vec<Block>, MapDecodeError>>()?;
+
+        // The length was checked above, so the shape always fits
+        Ok(IsoMap(
+            Array3::from_shape_vec((len_x, len_y, len_z), blocks).unwrap(),
+        ))
+    }
+
+    /// Save the map to a file at `path`, in the format used by
+    /// [`to_bytes`](#method.to_bytes)
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// Load a map saved with [`save`](#method.save) from the file at `path`
+    pub fn load(path: &Path) -> Result<IsoMap, MapDecodeError> {
+        use failure::ResultExt;
+
+        let bytes = fs::read(path).context(MapDecodeErrorKind::IoError)?;
+
+        IsoMap::from_bytes(&bytes)
+    }
+}
+
+/// An `IsoMap` as it's seen by serde
+#[derive(Serialize, Deserialize)]
+struct SerdeMap {
+    shape: (usize, usize, usize),
+    blocks: Vec<Block>,
+}
+
+impl Serialize for IsoMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeMap {
+            shape: self.shape(),
+            blocks: self.0.iter().cloned().collect(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for IsoMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IsoMap, D::Error> {
+        let SerdeMap { shape, blocks } = SerdeMap::deserialize(deserializer)?;
+        let len = blocks.len();
+
+        Array3::from_shape_vec(shape, blocks)
+            .map(IsoMap)
+            .map_err(|_| {
+                D::Error::custom(format!("a map of shape {:?} can't have {} blocks", shape, len))
+            })
+    }
+}
+
+/// Get the value a block is encoded as
+///
+/// These values are part of the format, so they must never change. Only custom
+/// blocks need more than one byte.
+fn block_to_code(block: Block) -> u16 {
+    match block {
+        Block::Air => 0,
+        Block::Rock => 1,
+        Block::Grass => 2,
+        Block::Soil => 3,
+        Block::Water => 4,
+        Block::Sand => 5,
+        Block::Snow => 6,
+        Block::Custom(id) => 256 + u16::from(id),
+    }
+}
+
+/// Get the block encoded as `code`
+fn block_from_code(code: u16) -> Result<Block, MapDecodeError> {
+    match code {
+        0 => Ok(Block::Air),
+        1 => Ok(Block::Rock),
+        2 => Ok(Block::Grass),
+        3 => Ok(Block::Soil),
+        4 => Ok(Block::Water),
+        5 => Ok(Block::Sand),
+        6 => Ok(Block::Snow),
+        256..=511 => Ok(Block::Custom((code - 256) as u8)),
+        _ => Err(MapDecodeErrorKind::UnknownBlock(code).into()),
+    }
+}
+
+fn u32_to_bytes(n: u32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+fn u32_from_bytes(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |n, &byte| (n << 8) | u32::from(byte))
+}
+
+/// An error with loading or decoding a map
+#[derive(Debug)]
+pub struct MapDecodeError {
+    inner: Context<MapDecodeErrorKind>,
+}
+
+impl MapDecodeError {
+    pub fn kind(&self) -> &MapDecodeErrorKind {
+        self.inner.get_context()
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Fail)]
+pub enum MapDecodeErrorKind {
+    #[fail(display = "A problem with reading the map file")]
+    IoError,
+
+    #[fail(display = "The data doesn't start with the map magic bytes")]
+    BadMagic,
+
+    #[fail(display = "The map is in unsupported format version {}", _0)]
+    VersionMismatch(u8),
+
+    #[fail(
+        display = "The map should be {} bytes long, but is {} bytes long",
+        expected, actual
+    )]
+    LengthMismatch { expected: usize, actual: usize },
+
+    #[fail(display = "The map contains an unknown block {}", _0)]
+    UnknownBlock(u16),
+}
+
+impl Fail for MapDecodeError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.inner.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.inner.backtrace()
+    }
+}
+
+impl Display for MapDecodeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(&self.inner, f)
+    }
+}
+
+impl From<MapDecodeErrorKind> for MapDecodeError {
+    fn from(kind: MapDecodeErrorKind) -> MapDecodeError {
+        MapDecodeError {
+            inner: Context::new(kind),
+        }
+    }
+}
+
+impl From<Context<MapDecodeErrorKind>> for MapDecodeError {
+    fn from(inner: Context<MapDecodeErrorKind>) -> MapDecodeError {
+        MapDecodeError { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn sample_map() -> IsoMap {
+        let mut iso_map = IsoMap::new_empty_dims(3, 4, 5);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[2, 3, 4]] = Block::Water;
+        iso_map.0[[1, 2, 3]] = Block::Sand;
+        iso_map
+    }
+
+    #[test]
+    fn round_trip() {
+        let iso_map = sample_map();
+        let bytes = iso_map.to_bytes();
+
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * 4 * 5);
+        assert_eq!(IsoMap::from_bytes(&bytes).unwrap().0, iso_map.0);
+    }
+
+    #[test]
+    fn custom_blocks_round_trip() {
+        let mut iso_map = sample_map();
+        iso_map.0[[1, 1, 1]] = Block::Custom(0);
+        iso_map.0[[2, 2, 2]] = Block::Custom(255);
+        let bytes = iso_map.to_bytes();
+
+        assert_eq!(bytes[4], VERSION_WIDE);
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * 4 * 5 * 2);
+        assert_eq!(IsoMap::from_bytes(&bytes).unwrap().0, iso_map.0);
+
+        // Without custom blocks, maps stay in the original format
+        assert_eq!(sample_map().to_bytes()[4], VERSION);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        use serde_json;
+
+        let mut iso_map = sample_map();
+        iso_map.0[[1, 1, 1]] = Block::Custom(9);
+
+        let json = serde_json::to_string(&iso_map).unwrap();
+        assert_eq!(serde_json::from_str::<IsoMap>(&json).unwrap(), iso_map);
+
+        let wrong_shape = json.replace("[3,4,5]", "[3,4,6]");
+        assert!(serde_json::from_str::<IsoMap>(&wrong_shape).is_err());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut path = env::temp_dir();
+        path.push(format!("cubeglobe-test-{}.map", std::process::id()));
+
+        let iso_map = sample_map();
+        iso_map.save(&path).unwrap();
+        let loaded = IsoMap::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().0, iso_map.0);
+
+        match IsoMap::load(&path) {
+            Err(e) => assert_eq!(e.kind(), &MapDecodeErrorKind::IoError),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn decode_errors() {
+        let bytes = sample_map().to_bytes();
+        let kind = |bytes: &[u8]| IsoMap::from_bytes(bytes).err().unwrap().kind().clone();
+
+        assert_eq!(kind(b"PNG"), MapDecodeErrorKind::BadMagic);
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 9;
+        assert_eq!(kind(&wrong_version), MapDecodeErrorKind::VersionMismatch(9));
+
+        assert_eq!(
+            kind(&bytes[..bytes.len() - 1]),
+            MapDecodeErrorKind::LengthMismatch {
+                expected: bytes.len(),
+                actual: bytes.len() - 1,
+            }
+        );
+
+        let mut unknown = bytes.clone();
+        unknown[HEADER_LEN] = 200;
+        assert_eq!(kind(&unknown), MapDecodeErrorKind::UnknownBlock(200));
+    }
+}
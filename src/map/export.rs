@@ -0,0 +1,188 @@
+//! Exporting maps to formats used by other programs
+//!
+//! ## Example use
+//! ```
+//! use cubeglobe::map::export;
+//! use cubeglobe::map::generator::{Generator, TerGenOne};
+//!
+//! let iso_map = TerGenOne::new().set_len(16).generate();
+//! let mut obj = Vec::new();
+//! export::to_obj(&iso_map, &mut obj).unwrap();
+//! ```
+
+use std::io;
+use std::io::Write;
+
+use map::{Block, IsoMap};
+
+/// A face of a unit cube, as the offset to the neighbouring block on that side
+/// and the corners of the face, counter-clockwise as seen from outside the cube
+type Face = ((isize, isize, isize), [(usize, usize, usize); 4]);
+
+/// The six faces of a unit cube
+const FACES: [Face; 6] = [
+    ((1, 0, 0), [(1, 0, 0), (1, 1, 0), (1, 1, 1), (1, 0, 1)]),
+    ((-1, 0, 0), [(0, 0, 0), (0, 0, 1), (0, 1, 1), (0, 1, 0)]),
+    ((0, 1, 0), [(0, 1, 0), (0, 1, 1), (1, 1, 1), (1, 1, 0)]),
+    ((0, -1, 0), [(0, 0, 0), (1, 0, 0), (1, 0, 1), (0, 0, 1)]),
+    ((0, 0, 1), [(0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1)]),
+    ((0, 0, -1), [(0, 0, 0), (0, 1, 0), (1, 1, 0), (1, 0, 0)]),
+];
+
+/// Write the map to `writer` as a Wavefront OBJ mesh
+///
+/// Every block other than air becomes a unit cube. Faces which can't be seen
+/// are left out: those against an opaque block, and those between two blocks
+/// of the same kind, such as inside a body of water. Faces are grouped by
+/// block, with a material named after the block (`rock`, `water`, and so on)
+/// set for each group, so that they can be given colors after importing.
+///
+/// OBJ files are conventionally y-up, so the map's z axis becomes the y axis of
+/// the mesh, and its y axis becomes the negative z axis.
+pub fn to_obj(map: &IsoMap, writer: &mut impl Write) -> io::Result<()> {
+    let (len_x, len_y, len_z) = map.shape();
+    let mut vertex_count = 0;
+    let mut material = None;
+
+    writeln!(
+        writer,
+        "# Exported from cubeglobe, {}x{}x{} blocks",
+        len_x, len_y, len_z
+    )?;
+
+    for ((x, y, z), &block) in map.0.indexed_iter() {
+        if block == Block::Air {
+            continue;
+        }
+
+        for &((dx, dy, dz), corners) in &FACES {
+            let neighbour = neighbour(map, (x, y, z), (dx, dy, dz));
+            let hidden = match neighbour {
+                Some(neighbour) => neighbour.is_opaque() || neighbour == block,
+                None => false,
+            };
+
+            if hidden {
+                continue;
+            }
+
+            if material != Some(block) {
+                writeln!(writer, "usemtl {}", material_name(block))?;
+                material = Some(block);
+            }
+
+            for &(cx, cy, cz) in &corners {
+                writeln!(writer, "v {} {} {}", x + cx, z + cz, -((y + cy) as isize))?;
+            }
+
+            writeln!(
+                writer,
+                "f {} {} {} {}",
+                vertex_count + 1,
+                vertex_count + 2,
+                vertex_count + 3,
+                vertex_count + 4
+            )?;
+            vertex_count += 4;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the block next to `pos` in the direction `offset`, or `None` if that's
+/// outside of the map
+fn neighbour(
+    map: &IsoMap,
+    pos: (usize, usize, usize),
+    offset: (isize, isize, isize),
+) -> Option<Block> {
+    let step = |index: usize, by: isize| (index as isize + by) as usize;
+
+    // Stepping back from 0 wraps around to a huge index, which `get` rejects
+    map.get(
+        step(pos.0, offset.0),
+        step(pos.1, offset.1),
+        step(pos.2, offset.2),
+    )
+}
+
+/// Get the name of the material used for `block`
+///
+/// Custom blocks are named after their id, such as `custom_3`.
+fn material_name(block: Block) -> String {
+    match block {
+        Block::Custom(id) => format!("custom_{}", id),
+        _ => block.to_string().to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(map: &IsoMap) -> String {
+        let mut obj = Vec::new();
+        to_obj(map, &mut obj).unwrap();
+        String::from_utf8(obj).unwrap()
+    }
+
+    fn count_lines(obj: &str, prefix: &str) -> usize {
+        obj.lines().filter(|line| line.starts_with(prefix)).count()
+    }
+
+    #[test]
+    fn single_cube() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0[[1, 1, 1]] = Block::Rock;
+        let obj = export(&iso_map);
+
+        assert_eq!(count_lines(&obj, "v "), 24);
+        assert_eq!(count_lines(&obj, "f "), 6);
+        assert_eq!(count_lines(&obj, "usemtl rock"), 1);
+        assert!(obj.lines().any(|line| line == "f 21 22 23 24"));
+    }
+
+    #[test]
+    fn hidden_faces_left_out() {
+        let mut iso_map = IsoMap::new_empty_dims(3, 1, 1);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[1, 0, 0]] = Block::Rock;
+        assert_eq!(count_lines(&export(&iso_map), "f "), 10);
+
+        // Rock can be seen through water, but not the other way around
+        iso_map.0[[2, 0, 0]] = Block::Water;
+        let obj = export(&iso_map);
+        assert_eq!(count_lines(&obj, "f "), 15);
+        assert_eq!(count_lines(&obj, "usemtl water"), 1);
+
+        // Two blocks of water make one body of water
+        iso_map.0[[1, 0, 0]] = Block::Water;
+        assert_eq!(count_lines(&export(&iso_map), "f "), 6 + 4 + 5);
+    }
+
+    #[test]
+    fn custom_materials() {
+        let mut iso_map = IsoMap::new_empty_dims(2, 1, 1);
+        iso_map.0[[0, 0, 0]] = Block::Custom(3);
+        iso_map.0[[1, 0, 0]] = Block::Custom(12);
+        let obj = export(&iso_map);
+
+        assert_eq!(count_lines(&obj, "usemtl custom_3"), 1);
+        assert_eq!(count_lines(&obj, "usemtl custom_12"), 1);
+        assert_eq!(count_lines(&obj, "f "), 10);
+    }
+
+    #[test]
+    fn z_is_up() {
+        let mut iso_map = IsoMap::new_empty_dims(1, 1, 3);
+        iso_map.0[[0, 0, 2]] = Block::Sand;
+        let obj = export(&iso_map);
+
+        assert!(obj.lines().any(|line| line == "v 1 3 -1"));
+        for line in obj.lines().filter(|line| line.starts_with("v ")) {
+            let height = line.split(' ').nth(2).unwrap();
+            assert!(height == "2" || height == "3");
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! A generator for carving caves out of solid rock
+
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+use rand::{Rng, RngCore};
+
+use map::generator::{seeded_rng, validate_dims, Generator, GeneratorError};
+use map::{Block, IsoMap};
+
+/// A generator which carves caves out of a solid block of rock using 3D noise.
+///
+/// The noise is sampled at every block, and the block is carved out wherever
+/// the noise exceeds the threshold. The top layer of the map is never carved,
+/// so the surface stays solid.
+///
+/// The noise values mostly fall between -1 and 1, so the threshold determines
+/// how much of the rock is carved: around 0, about half of the volume becomes
+/// caves, and the caves are wide and mostly connected; towards 1, only small,
+/// isolated pockets remain. The frequency determines the size of the caves.
+/// Lower frequencies produce larger caverns, which are more likely to connect
+/// into systems at a given threshold, while higher frequencies produce lots of
+/// small chambers.
+///
+/// ## Example use
+/// ```
+/// use cubeglobe::map::generator::{CaveGen, Generator};
+///
+/// let gen = CaveGen::new().set_len(32).set_threshold(0.2);
+/// let iso_map = gen.generate();
+/// ```
+#[derive(Debug)]
+pub struct CaveGen {
+    dims: (usize, usize, usize),
+    frequency: f64,
+    threshold: f64,
+    seed: Option<u64>,
+}
+
+impl CaveGen {
+    const DEFAULT_LEN: usize = 64;
+    const DEFAULT_FREQUENCY: f64 = 0.05;
+    const DEFAULT_THRESHOLD: f64 = 0.3;
+
+    /// Set the edge length, making the map a cube
+    pub fn set_len(self, len: usize) -> CaveGen {
+        CaveGen {
+            dims: (len, len, len),
+            ..self
+        }
+    }
+
+    /// Set the lengths of the map along the x, y and z axes
+    pub fn set_dims(self, x: usize, y: usize, z: usize) -> CaveGen {
+        CaveGen {
+            dims: (x, y, z),
+            ..self
+        }
+    }
+
+    /// Set the frequency parameter for the noise generator
+    ///
+    /// Lower values produce larger caves.
+    pub fn set_frequency(self, freq: f64) -> CaveGen {
+        CaveGen {
+            frequency: freq,
+            ..self
+        }
+    }
+
+    /// Set the threshold above which blocks are carved out
+    ///
+    /// Higher values produce fewer and smaller caves. At 1 and above, there
+    /// will be practically no caves at all.
+    pub fn set_threshold(self, threshold: f64) -> CaveGen {
+        CaveGen { threshold, ..self }
+    }
+
+    /// Set the seed for the random number generator
+    ///
+    /// Generators with the same seed and parameters will always produce the
+    /// same map. Without a seed, every map is different.
+    pub fn set_seed(self, seed: u64) -> CaveGen {
+        CaveGen {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Get a new cave generator with all default settings
+    pub fn new() -> CaveGen {
+        CaveGen {
+            dims: (Self::DEFAULT_LEN, Self::DEFAULT_LEN, Self::DEFAULT_LEN),
+            frequency: Self::DEFAULT_FREQUENCY,
+            threshold: Self::DEFAULT_THRESHOLD,
+            seed: None,
+        }
+    }
+}
+
+impl Default for CaveGen {
+    fn default() -> CaveGen {
+        CaveGen::new()
+    }
+}
+
+impl Generator for CaveGen {
+    fn generate(&self) -> IsoMap {
+        self.generate_with_rng(&mut seeded_rng(self.seed))
+    }
+
+    fn validate(&self) -> Result<(), GeneratorError> {
+        validate_dims(self.dims)
+    }
+
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap {
+        let noise = Fbm::new().set_seed(rng.gen()).set_frequency(self.frequency);
+
+        let (len_x, len_y, len_z) = self.dims;
+        let mut isomap = IsoMap::new_empty_dims(len_x, len_y, len_z);
+        isomap.0.fill(Block::Rock);
+
+        // Everything below the top layer can be carved
+        let carve_height = len_z.saturating_sub(1);
+
+        for ((x, y, z), block) in isomap.0.indexed_iter_mut() {
+            if z < carve_height && noise.get([x as f64, y as f64, z as f64]) > self.threshold {
+                *block = Block::Air;
+            }
+        }
+
+        isomap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Axis;
+
+    #[test]
+    fn high_threshold_is_solid() {
+        let map = CaveGen::new().set_len(16).set_threshold(2.0).generate();
+
+        assert!(map.0.iter().all(|b| b == &Block::Rock));
+    }
+
+    #[test]
+    fn surface_stays_solid() {
+        let map = CaveGen::new().set_len(16).set_threshold(-2.0).generate();
+
+        assert!(map.0.index_axis(Axis(2), 15).iter().all(|b| b == &Block::Rock));
+        assert!(map.0.index_axis(Axis(2), 0).iter().all(|b| b == &Block::Air));
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let gen = CaveGen::new().set_len(16).set_seed(1234);
+
+        assert_eq!(gen.generate().0, gen.generate().0);
+    }
+}
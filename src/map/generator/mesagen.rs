@@ -0,0 +1,233 @@
+//! A terrain generator for making terraced landscapes
+
+use rand::{Rng, RngCore};
+
+use map::generator::noise_kind::Fractal;
+use map::generator::{heightmap, seeded_rng, validate_dims, Generator, GeneratorError, NoiseKind};
+use map::{Block, IsoMap};
+
+/// A terrain generator which makes flat-topped plateaus and terraces
+///
+/// `MesaGen` uses the same kind of heightmap as
+/// [`TerGenOne`](struct.TerGenOne.html), but rounds the height of every column
+/// to the nearest multiple of a step, so the landscape rises in flat layers.
+/// The plateaus are bare rock unless capping is turned on, in which case each
+/// is topped with a layer of grass over soil.
+///
+/// ## Example use
+/// ```
+/// use cubeglobe::map::generator::{MesaGen, Generator};
+///
+/// let gen = MesaGen::new().set_len(32).set_step(4).set_cap(true);
+/// let iso_map = gen.generate();
+/// ```
+#[derive(Debug)]
+pub struct MesaGen {
+    /// Dimensions of the map, along the x, y and z axes
+    dims: (usize, usize, usize),
+    frequency: f64,
+    step: usize,
+    cap: bool,
+    seed: Option<u64>,
+}
+
+impl MesaGen {
+    /// Default dimension for a map
+    const DEFAULT_LEN: usize = 64;
+
+    /// Default frequency parameter for the noise generator
+    const DEFAULT_FREQUENCY: f64 = 0.02;
+
+    /// Default height of a single terrace
+    const DEFAULT_STEP: usize = 4;
+
+    /// Set the edge length, making the map a cube
+    pub fn set_len(self, len: usize) -> MesaGen {
+        MesaGen {
+            dims: (len, len, len),
+            ..self
+        }
+    }
+
+    /// Set the lengths of the map along the x, y and z axes
+    pub fn set_dims(self, x: usize, y: usize, z: usize) -> MesaGen {
+        MesaGen {
+            dims: (x, y, z),
+            ..self
+        }
+    }
+
+    /// Set the frequency parameter for the noise generator
+    ///
+    /// Lower frequencies than for [`TerGenOne`](struct.TerGenOne.html) work
+    /// best, since wide plateaus need gentle slopes underneath.
+    pub fn set_frequency(self, freq: f64) -> MesaGen {
+        MesaGen {
+            frequency: freq,
+            ..self
+        }
+    }
+
+    /// Set the height of a single terrace, in blocks
+    ///
+    /// A step of 1 leaves the heightmap as it is.
+    pub fn set_step(self, step: usize) -> MesaGen {
+        MesaGen { step, ..self }
+    }
+
+    /// Set whether the top of every plateau is capped with grass over soil,
+    /// instead of being bare rock
+    pub fn set_cap(self, cap: bool) -> MesaGen {
+        MesaGen { cap, ..self }
+    }
+
+    /// Set the seed for the random number generator
+    ///
+    /// Generators with the same seed and parameters will always produce the
+    /// same map. Without a seed, every map is different.
+    pub fn set_seed(self, seed: u64) -> MesaGen {
+        MesaGen {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Get a new terrain generator with all default settings
+    pub fn new() -> MesaGen {
+        MesaGen {
+            dims: (Self::DEFAULT_LEN, Self::DEFAULT_LEN, Self::DEFAULT_LEN),
+            frequency: Self::DEFAULT_FREQUENCY,
+            step: Self::DEFAULT_STEP,
+            cap: false,
+            seed: None,
+        }
+    }
+}
+
+impl Default for MesaGen {
+    fn default() -> MesaGen {
+        MesaGen::new()
+    }
+}
+
+impl Generator for MesaGen {
+    fn generate(&self) -> IsoMap {
+        self.generate_with_rng(&mut seeded_rng(self.seed))
+    }
+
+    fn validate(&self) -> Result<(), GeneratorError> {
+        validate_dims(self.dims)?;
+
+        if self.step == 0 {
+            return Err(GeneratorError::ZeroStep);
+        }
+
+        Ok(())
+    }
+
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap {
+        let fractal = Fractal {
+            frequency: self.frequency,
+            ..Fractal::default()
+        };
+        let noise = NoiseKind::Fbm.build(rng.gen(), &fractal);
+        let (len_x, len_y, len_z) = self.dims;
+        let step = self.step.max(1);
+        let heights = heightmap(&*noise, self.dims);
+
+        let mut isomap = IsoMap::new_empty_dims(len_x, len_y, len_z);
+
+        for ((x, y), &height) in heights.indexed_iter() {
+            // round to the nearest step, then back down to a whole step if
+            // that went above the map
+            let mut height = (height + step / 2) / step * step;
+            if height > len_z {
+                height -= step;
+            }
+
+            let mut column = isomap.0.slice_mut(s![x, y, 0..height]);
+            column.fill(Block::Rock);
+
+            if self.cap && height > 0 {
+                column[height - 1] = Block::Grass;
+                if height > 1 {
+                    column[height - 2] = Block::Soil;
+                }
+            }
+        }
+
+        isomap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::generator::surface_height;
+
+    #[test]
+    fn same_seed_same_map() {
+        let gen = MesaGen::new().set_len(16).set_seed(1234);
+
+        assert_eq!(gen.generate().0, gen.generate().0);
+    }
+
+    #[test]
+    fn heights_are_steps() {
+        for &(len, step) in &[(32, 4), (30, 7), (16, 1)] {
+            let map = MesaGen::new()
+                .set_len(len)
+                .set_step(step)
+                .set_frequency(0.05)
+                .set_seed(3)
+                .generate();
+
+            for x in 0..len {
+                for y in 0..len {
+                    let height = surface_height(&map, x, y);
+                    assert_eq!(height % step, 0);
+                    assert!(height <= len);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn capped_plateaus() {
+        let gen = MesaGen::new().set_len(24).set_step(3).set_seed(5);
+        let bare = gen.generate();
+        let capped = gen.set_cap(true).generate();
+
+        for x in 0..24 {
+            for y in 0..24 {
+                let height = surface_height(&bare, x, y);
+                assert_eq!(surface_height(&capped, x, y), height);
+
+                if height > 1 {
+                    assert_eq!(capped.0[[x, y, height - 1]], Block::Grass);
+                    assert_eq!(capped.0[[x, y, height - 2]], Block::Soil);
+                    assert_eq!(bare.0[[x, y, height - 1]], Block::Rock);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn progress_when_done() {
+        let gen = MesaGen::new().set_len(8).set_seed(2);
+        let mut reports = Vec::new();
+        let map = gen.generate_with_progress(&mut |done| reports.push(done));
+
+        assert_eq!(map, gen.generate());
+        assert_eq!(reports, vec![1.0]);
+    }
+
+    #[test]
+    fn zero_step() {
+        assert_eq!(
+            MesaGen::new().set_len(8).set_step(0).try_generate().err(),
+            Some(GeneratorError::ZeroStep)
+        );
+        assert!(MesaGen::new().set_len(8).try_generate().is_ok());
+    }
+}
@@ -1,17 +1,147 @@
 //! Generators for procedurally generating [`IsoMap`s](struct.IsoMap.html)
 
+mod cavegen;
+mod mesagen;
+mod noise_kind;
+mod pipeline;
+mod rivers;
 mod tergenone;
 mod tergentwo;
 mod testing;
+mod vegetation;
+mod water;
 
+pub use map::generator::cavegen::CaveGen;
+pub use map::generator::mesagen::MesaGen;
+pub use map::generator::noise_kind::NoiseKind;
+pub use map::generator::pipeline::Pipeline;
+pub use map::generator::rivers::carve_river;
 pub use map::generator::testing::TestingGenerator;
 pub use map::generator::tergenone::TerGenOne;
-pub use map::generator::tergentwo::TerGenTwo;
+pub use map::generator::tergentwo::{GenerationInfo, TerGenTwo};
+pub use map::generator::vegetation::{scatter_structures, scatter_trees, tree};
+pub use map::generator::water::flood_fill_water;
 
-use map::IsoMap;
+use failure::Fail;
+use ndarray::Array2;
+use noise::NoiseFn;
+use rand::rngs::StdRng;
+use rand::{thread_rng, FromEntropy, RngCore, SeedableRng};
+
+use map::{Block, IsoMap};
 
 /// A generator capable of returning an
 /// [`IsoMap`](map/struct.IsoMap.html).
 pub trait Generator {
-    fn generate(&self) -> IsoMap;
+    /// Generate a map
+    ///
+    /// By default, this uses a thread RNG. Generators which can be seeded use
+    /// their own seed instead, if they have one.
+    fn generate(&self) -> IsoMap {
+        self.generate_with_rng(&mut thread_rng())
+    }
+
+    /// Generate a map, taking all randomness from `rng`
+    ///
+    /// This ignores any seed set on the generator itself, so that many
+    /// generators can be driven deterministically from a single shared RNG.
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap;
+
+    /// Generate a map, calling `progress` with the fraction of it done so far
+    ///
+    /// Generators which build maps one slice along the x axis at a time call
+    /// `progress` after every slice. By default, `progress` is only called
+    /// once, with 1, when the map is done. The map is the same one
+    /// [`generate`](#method.generate) would make.
+    fn generate_with_progress(&self, progress: &mut dyn FnMut(f32)) -> IsoMap {
+        let map = self.generate();
+        progress(1.0);
+
+        map
+    }
+
+    /// Check that the generator's settings can produce a sensible map
+    ///
+    /// By default, every setting is valid.
+    fn validate(&self) -> Result<(), GeneratorError> {
+        Ok(())
+    }
+
+    /// Generate a map, or return an error if the settings are invalid
+    ///
+    /// Unlike [`generate`](#method.generate), which does its best with any
+    /// settings, this checks them with [`validate`](#method.validate) first.
+    fn try_generate(&self) -> Result<IsoMap, GeneratorError> {
+        self.validate()?;
+
+        Ok(self.generate())
+    }
+}
+
+/// A problem with the settings of a generator
+#[derive(Fail, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorError {
+    #[fail(display = "The map has a length of 0 along at least one axis")]
+    ZeroDimension,
+
+    #[fail(display = "The map size {} is below the minimum of {}", len, min)]
+    TooSmall { len: usize, min: usize },
+
+    #[fail(
+        display = "The minimum soil cutoff {} is not below the map height {}",
+        min_soil_cutoff, height
+    )]
+    SoilCutoffTooHigh { min_soil_cutoff: usize, height: usize },
+
+    #[fail(
+        display = "The maximum water level {} is not below the map height {}",
+        max_water_level, height
+    )]
+    WaterLevelTooHigh { max_water_level: usize, height: usize },
+
+    #[fail(display = "The terrace step is 0")]
+    ZeroStep,
+}
+
+/// Check that none of the lengths in `dims` are 0
+fn validate_dims(dims: (usize, usize, usize)) -> Result<(), GeneratorError> {
+    match dims {
+        (0, _, _) | (_, 0, _) | (_, _, 0) => Err(GeneratorError::ZeroDimension),
+        _ => Ok(()),
+    }
+}
+
+/// Get the RNG a generator should use, seeded with `seed` if there is one, or
+/// from entropy otherwise
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// Sample `noise` into a heightmap for a map with dimensions `dims`
+///
+/// Noise values between -1 and 1 are mapped to heights around the middle of
+/// the map, capped at its height.
+fn heightmap(noise: &dyn NoiseFn<[f64; 2]>, dims: (usize, usize, usize)) -> Array2<usize> {
+    let (len_x, len_y, len_z) = dims;
+    let half_height: f64 = len_z as f64 / 2.0;
+
+    Array2::from_shape_fn((len_x, len_y), |(x, y)| {
+        let height = (half_height + ((noise.get([x as f64, y as f64])) * half_height)) as usize;
+        height.min(len_z)
+    })
+}
+
+/// Get the height of the column at `x`, `y`, that is, one above the index of
+/// the topmost block that isn't air, or 0 if the column is empty
+fn surface_height(map: &IsoMap, x: usize, y: usize) -> usize {
+    map.0
+        .slice(s![x, y, ..])
+        .indexed_iter()
+        .filter(|&(_, block)| block != &Block::Air)
+        .map(|(z, _)| z + 1)
+        .last()
+        .unwrap_or(0)
 }
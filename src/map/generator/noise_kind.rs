@@ -0,0 +1,143 @@
+//! Choice of noise function for generating heightmaps
+
+use noise::{Billow, Fbm, MultiFractal, NoiseFn, RidgedMulti, Seedable, Value};
+
+/// The kind of noise a terrain generator uses for its heightmap
+///
+/// The frequency set on a generator is passed on to the noise function, but
+/// every kind of noise responds to it differently, so a frequency which works
+/// well for one kind may produce very different terrain with another.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum NoiseKind {
+    /// Fractal Brownian motion, producing rolling hills and mountains
+    #[default]
+    Fbm,
+
+    /// Billowy noise, producing rounded, lumpy hills
+    Billow,
+
+    /// Ridged multifractal noise, producing sharp ridges and valleys
+    RidgedMulti,
+
+    /// Plain value noise, producing blocky, uneven terrain. The frequency
+    /// scales the input points, since value noise has no frequency of its own.
+    Value,
+}
+
+/// Settings for fractal noise functions
+///
+/// Settings which are `None` are left at the noise function's own default.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Fractal {
+    pub frequency: f64,
+    pub octaves: Option<usize>,
+    pub persistence: Option<f64>,
+    pub lacunarity: Option<f64>,
+}
+
+impl Fractal {
+    /// Apply the settings to `noise`
+    fn apply<T: MultiFractal>(&self, mut noise: T) -> T {
+        noise = noise.set_frequency(self.frequency);
+
+        if let Some(octaves) = self.octaves {
+            noise = noise.set_octaves(octaves);
+        }
+        if let Some(persistence) = self.persistence {
+            noise = noise.set_persistence(persistence);
+        }
+        if let Some(lacunarity) = self.lacunarity {
+            noise = noise.set_lacunarity(lacunarity);
+        }
+
+        noise
+    }
+}
+
+impl NoiseKind {
+    /// Make a noise function of this kind
+    ///
+    /// Value noise isn't fractal, so it only uses the frequency.
+    pub(crate) fn build(self, seed: u32, fractal: &Fractal) -> Box<dyn NoiseFn<[f64; 2]>> {
+        match self {
+            NoiseKind::Fbm => Box::new(fractal.apply(Fbm::new().set_seed(seed))),
+            NoiseKind::Billow => Box::new(fractal.apply(Billow::new().set_seed(seed))),
+            NoiseKind::RidgedMulti => Box::new(fractal.apply(RidgedMulti::new().set_seed(seed))),
+            NoiseKind::Value => Box::new(Scaled {
+                source: Value::new().set_seed(seed),
+                frequency: fractal.frequency,
+            }),
+        }
+    }
+}
+
+/// A noise function with its input points scaled by `frequency`
+struct Scaled<N> {
+    source: N,
+    frequency: f64,
+}
+
+impl<N: NoiseFn<[f64; 2]>> NoiseFn<[f64; 2]> for Scaled<N> {
+    fn get(&self, point: [f64; 2]) -> f64 {
+        self.source
+            .get([point[0] * self.frequency, point[1] * self.frequency])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_differ() {
+        let point = [12.3, 45.6];
+        let values: Vec<f64> = [
+            NoiseKind::Fbm,
+            NoiseKind::Billow,
+            NoiseKind::RidgedMulti,
+            NoiseKind::Value,
+        ].iter()
+            .map(|kind| {
+                let fractal = Fractal {
+                    frequency: 0.05,
+                    ..Fractal::default()
+                };
+                kind.build(1, &fractal).get(point)
+            })
+            .collect();
+
+        for (i, a) in values.iter().enumerate() {
+            for b in &values[i + 1..] {
+                assert!(a != b);
+            }
+        }
+    }
+
+    #[test]
+    fn unset_settings_are_defaults() {
+        let fractal = Fractal {
+            frequency: 0.05,
+            ..Fractal::default()
+        };
+        let fbm = Fbm::new().set_seed(3).set_frequency(0.05);
+        let built = NoiseKind::Fbm.build(3, &fractal);
+
+        for &point in &[[0.5, 1.5], [10.0, -3.0], [100.1, 7.7]] {
+            assert_eq!(built.get(point), fbm.get(point));
+        }
+
+        let rough = Fractal {
+            octaves: Some(2),
+            persistence: Some(0.9),
+            lacunarity: Some(3.0),
+            ..fractal
+        };
+        let expected = Fbm::new()
+            .set_seed(3)
+            .set_frequency(0.05)
+            .set_octaves(2)
+            .set_persistence(0.9)
+            .set_lacunarity(3.0);
+        assert_eq!(NoiseKind::Fbm.build(3, &rough).get([1.1, 2.2]), expected.get([1.1, 2.2]));
+    }
+}
@@ -0,0 +1,140 @@
+//! A generator which runs another and then changes its maps
+
+use rand::RngCore;
+
+use map::generator::{Generator, GeneratorError};
+use map::IsoMap;
+
+/// A function which changes a finished map
+type Decorator = dyn Fn(&mut IsoMap);
+
+/// A generator which makes maps with another generator, and then passes them
+/// through a list of decorators, in the order they were added
+///
+/// Decorators are functions which change a finished map, such as
+/// [`scatter_trees`](fn.scatter_trees.html) or
+/// [`carve_river`](fn.carve_river.html), so a pipeline is a recipe for a kind
+/// of map which can be set up once and used again and again. Decorators don't
+/// get an RNG, so ones which need randomness should be seeded, and the
+/// pipeline only makes the same map twice if they are and the base generator
+/// does as well.
+///
+/// ## Example use
+/// ```
+/// use cubeglobe::map::generator::{scatter_trees, Generator, Pipeline, TerGenTwo};
+/// use cubeglobe::map::Block;
+///
+/// let terrain = TerGenTwo::new()
+///     .set_len(32)
+///     .set_min_soil_cutoff(16)
+///     .set_max_water_level(8);
+///
+/// let pipeline = Pipeline::new(terrain)
+///     .add_decorator(|map| scatter_trees(map, 0.05, 1))
+///     .add_decorator(|map| map.replace(Block::Sand, Block::Soil));
+///
+/// let iso_map = pipeline.generate();
+/// ```
+pub struct Pipeline {
+    base: Box<dyn Generator>,
+    decorators: Vec<Box<Decorator>>,
+}
+
+impl Pipeline {
+    /// Start a pipeline which makes maps with `base`
+    pub fn new<G: Generator + 'static>(base: G) -> Pipeline {
+        Pipeline {
+            base: Box::new(base),
+            decorators: Vec::new(),
+        }
+    }
+
+    /// Add a decorator to run on every map after the ones already added
+    pub fn add_decorator<F: Fn(&mut IsoMap) + 'static>(mut self, decorator: F) -> Pipeline {
+        self.decorators.push(Box::new(decorator));
+        self
+    }
+
+    /// Run every decorator on `map`
+    fn decorate(&self, mut map: IsoMap) -> IsoMap {
+        for decorator in &self.decorators {
+            decorator(&mut map);
+        }
+
+        map
+    }
+}
+
+impl Generator for Pipeline {
+    /// The base generator's own seed is used, if it has one
+    fn generate(&self) -> IsoMap {
+        self.decorate(self.base.generate())
+    }
+
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap {
+        self.decorate(self.base.generate_with_rng(rng))
+    }
+
+    /// Progress is reported by the base generator, so the decorators run after
+    /// it reports that the map is done
+    fn generate_with_progress(&self, progress: &mut dyn FnMut(f32)) -> IsoMap {
+        self.decorate(self.base.generate_with_progress(progress))
+    }
+
+    /// Only the base generator has settings to check
+    fn validate(&self) -> Result<(), GeneratorError> {
+        self.base.validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::generator::{flood_fill_water, TerGenTwo, TestingGenerator};
+    use map::Block;
+
+    #[test]
+    fn decorators_in_order() {
+        let pipeline = Pipeline::new(TestingGenerator { dim: 8 })
+            .add_decorator(|map| flood_fill_water(map, 5))
+            .add_decorator(|map| map.replace(Block::Rock, Block::Soil));
+        let map = pipeline.generate();
+
+        let mut expected = TestingGenerator { dim: 8 }.generate();
+        flood_fill_water(&mut expected, 5);
+        expected.replace(Block::Rock, Block::Soil);
+        assert_eq!(map, expected);
+
+        // Each decorator sees what the ones before it did
+        let chained = Pipeline::new(TestingGenerator { dim: 8 })
+            .add_decorator(|map| map.replace(Block::Rock, Block::Soil))
+            .add_decorator(|map| map.replace(Block::Soil, Block::Sand));
+        assert_eq!(chained.generate().block_counts()[&Block::Soil], 0);
+
+        let reversed = Pipeline::new(TestingGenerator { dim: 8 })
+            .add_decorator(|map| map.replace(Block::Soil, Block::Sand))
+            .add_decorator(|map| map.replace(Block::Rock, Block::Soil));
+        assert_eq!(reversed.generate().block_counts()[&Block::Sand], 0);
+    }
+
+    #[test]
+    fn base_settings_used() {
+        let gen = || TerGenTwo::new().set_len(16).set_min_soil_cutoff(8).set_max_water_level(4);
+        let pipeline = Pipeline::new(gen().set_seed(5))
+            .add_decorator(|map| map.replace(Block::Water, Block::Air));
+
+        let map = pipeline.generate();
+        assert_eq!(map, pipeline.generate());
+        assert_eq!(map.block_counts()[&Block::Water], 0);
+
+        let mut reports = 0;
+        assert_eq!(pipeline.generate_with_progress(&mut |_| reports += 1), map);
+        assert_eq!(reports, 16);
+
+        assert_eq!(
+            Pipeline::new(TestingGenerator { dim: 2 }).try_generate().err(),
+            Some(GeneratorError::TooSmall { len: 2, min: 6 })
+        );
+        assert!(Pipeline::new(gen().set_max_water_level(20)).validate().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! River network analysis over a heightmap, and carving rivers into maps
+//!
+//! Rivers are found by routing flow: every column drains towards its steepest
+//! downhill neighbour, and the number of columns draining through each column
+//! is accumulated. Columns with a large enough upstream area are considered to
+//! be part of a river, which naturally produces branching networks.
+//!
+//! Single rivers can also be carved into an already generated map with
+//! [`carve_river`](fn.carve_river.html).
+
+use ndarray::{Array2, ArrayViewMut1};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use map::generator::surface_height;
+use map::{Block, IsoMap};
+
+/// Offsets of the eight neighbours of a column
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (-1, 1),
+    (1, -1),
+    (1, 1),
+];
+
+/// Compute the flow direction of every column in `heights`
+///
+/// Each column drains into the neighbour with the steepest descent (the drop
+/// in height divided by the distance to the neighbour). Columns without any
+/// lower neighbour, such as pits and flat areas, are `None`.
+pub fn flow_directions(heights: &Array2<usize>) -> Array2<Option<(usize, usize)>> {
+    let (len_x, len_y) = heights.dim();
+
+    Array2::from_shape_fn((len_x, len_y), |(x, y)| {
+        let height = heights[[x, y]];
+        let mut steepest: Option<((usize, usize), f64)> = None;
+
+        for &(dx, dy) in &NEIGHBOURS {
+            let nx = x as isize + dx;
+            let ny = y as isize + dy;
+
+            if nx < 0 || ny < 0 || nx >= len_x as isize || ny >= len_y as isize {
+                continue;
+            }
+
+            let (nx, ny) = (nx as usize, ny as usize);
+            let neighbour_height = heights[[nx, ny]];
+
+            if neighbour_height >= height {
+                continue;
+            }
+
+            let distance = ((dx * dx + dy * dy) as f64).sqrt();
+            let slope = (height - neighbour_height) as f64 / distance;
+
+            match steepest {
+                Some((_, steepest_slope)) if steepest_slope >= slope => {}
+                _ => steepest = Some(((nx, ny), slope)),
+            }
+        }
+
+        steepest.map(|(pos, _)| pos)
+    })
+}
+
+/// Compute the upstream area of every column in `heights`
+///
+/// Every column contributes one unit of flow, which is passed along the flow
+/// directions. The result for a column is the number of columns (including
+/// itself) which drain through it.
+pub fn flow_accumulation(heights: &Array2<usize>) -> Array2<usize> {
+    let directions = flow_directions(heights);
+    let mut accumulation = Array2::from_elem(heights.dim(), 1);
+
+    // Flow only ever goes downhill, so visiting columns from the highest to the
+    // lowest guarantees that all upstream flow has arrived before it is passed
+    // on.
+    let mut order: Vec<(usize, usize)> = heights.indexed_iter().map(|(pos, _)| pos).collect();
+    order.sort_by(|a, b| heights[[b.0, b.1]].cmp(&heights[[a.0, a.1]]));
+
+    for (x, y) in order {
+        if let Some((dx, dy)) = directions[[x, y]] {
+            let flow = accumulation[[x, y]];
+            accumulation[[dx, dy]] += flow;
+        }
+    }
+
+    accumulation
+}
+
+/// Find the columns which are part of a river
+///
+/// A column is part of a river if at least `threshold` columns drain through
+/// it.
+pub fn river_columns(heights: &Array2<usize>, threshold: usize) -> Array2<bool> {
+    flow_accumulation(heights).mapv(|flow| flow >= threshold)
+}
+
+/// Carve a river channel into `column`, whose top block is at `height - 1`
+///
+/// The column is lowered by one block, and the new top block is replaced with
+/// water. A column only one block high just has that block turned to water.
+pub fn carve_channel(mut column: ArrayViewMut1<Block>, height: usize) {
+    // Lower the terrain, and put the water on top of what's left
+    if height >= 2 {
+        column[height - 1] = Block::Air;
+        column[height - 2] = Block::Water;
+    } else {
+        column[0] = Block::Water;
+    }
+}
+
+/// Carve a river into `map`, starting at the column at `start`
+///
+/// The river walks downhill, always moving to the neighbouring column with the
+/// lowest surface, with ties broken using an RNG seeded by `seed`. Along the
+/// way, the terrain is lowered by one block and the new top block is replaced
+/// with water. The river stops when it reaches the edge of the map, flows into
+/// existing water, or has nowhere lower to go.
+///
+/// This works on any map, regardless of which generator produced it.
+pub fn carve_river(map: &mut IsoMap, start: (usize, usize), seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (len_x, len_y, _) = map.shape();
+    let mut visited = Array2::from_elem((len_x, len_y), false);
+    let (mut x, mut y) = start;
+
+    while x < len_x && y < len_y && !visited[[x, y]] {
+        visited[[x, y]] = true;
+
+        let height = surface_height(map, x, y);
+        if height == 0 || map.0[[x, y, height - 1]] == Block::Water {
+            break;
+        }
+
+        carve_channel(map.0.slice_mut(s![x, y, ..]), height);
+
+        if x == 0 || y == 0 || x == len_x - 1 || y == len_y - 1 {
+            break;
+        }
+
+        let mut neighbours = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        rng.shuffle(&mut neighbours);
+
+        let next = neighbours
+            .iter()
+            .filter(|&&(nx, ny)| !visited[[nx, ny]])
+            .map(|&(nx, ny)| ((nx, ny), surface_height(map, nx, ny)))
+            .filter(|&(_, neighbour_height)| neighbour_height <= height)
+            .min_by_key(|&(_, neighbour_height)| neighbour_height);
+
+        match next {
+            Some(((nx, ny), _)) => {
+                x = nx;
+                y = ny;
+            }
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5×5 valley which slopes down towards x = 0 and has its lowest line
+    /// at y = 2.
+    fn valley() -> Array2<usize> {
+        Array2::from_shape_fn((5, 5), |(x, y)| {
+            if y > 2 {
+                x + 2 * (y - 2)
+            } else {
+                x + 2 * (2 - y)
+            }
+        })
+    }
+
+    #[test]
+    fn flow_goes_downhill() {
+        let heights = valley();
+        let directions = flow_directions(&heights);
+
+        for ((x, y), direction) in directions.indexed_iter() {
+            match *direction {
+                Some((dx, dy)) => assert!(heights[[dx, dy]] < heights[[x, y]]),
+                None => assert_eq!((x, y), (0, 2)),
+            }
+        }
+    }
+
+    #[test]
+    fn flow_accumulates_downhill() {
+        let heights = valley();
+        let accumulation = flow_accumulation(&heights);
+
+        // Everything drains to the bottom of the valley
+        assert_eq!(accumulation[[0, 2]], 25);
+
+        // Flow along the valley floor grows as we go downhill
+        for x in 0..4 {
+            assert!(accumulation[[x, 2]] > accumulation[[x + 1, 2]]);
+        }
+    }
+
+    #[test]
+    fn rivers_follow_accumulation() {
+        let heights = valley();
+        let accumulation = flow_accumulation(&heights);
+        let rivers = river_columns(&heights, 5);
+
+        for ((x, y), &is_river) in rivers.indexed_iter() {
+            assert_eq!(is_river, accumulation[[x, y]] >= 5);
+        }
+
+        // The river runs along the valley floor, not up the slopes
+        assert!(rivers[[0, 2]]);
+        assert!(!rivers[[4, 0]]);
+        assert!(!rivers[[4, 4]]);
+    }
+
+    #[test]
+    fn river_runs_downhill() {
+        // A slope that gets lower towards x = 0
+        let mut map = IsoMap::new_empty(8);
+        for x in 0..8 {
+            map.0.slice_mut(s![x, .., 0..(x + 1)]).fill(Block::Rock);
+        }
+
+        carve_river(&mut map, (6, 4), 1);
+
+        // The river goes straight down the slope, lowering it as it goes
+        for x in 0..7 {
+            let height = surface_height(&map, x, 4);
+            assert_eq!(height, x.max(1));
+            assert_eq!(map.0[[x, 4, height - 1]], Block::Water);
+        }
+
+        // Nothing else was touched
+        let water = map.0.iter().filter(|b| b == &&Block::Water).count();
+        assert_eq!(water, 7);
+    }
+
+    #[test]
+    fn river_stops_at_water() {
+        // A step down from x = 4 to a line of water at x = 3, so that the only
+        // way down from the start is into the water
+        let mut base = IsoMap::new_empty(8);
+        base.0.slice_mut(s![.., .., 0..2]).fill(Block::Rock);
+        base.0.slice_mut(s![4.., .., 2]).fill(Block::Rock);
+        base.0.slice_mut(s![3, .., 1]).fill(Block::Water);
+
+        for seed in 0..10 {
+            let mut map = base.clone();
+            carve_river(&mut map, (4, 4), seed);
+
+            // Only the starting column is carved before the river joins the
+            // water
+            assert_eq!(map.0[[4, 4, 2]], Block::Air);
+            assert_eq!(map.0[[4, 4, 1]], Block::Water);
+            let water = map.0.iter().filter(|b| b == &&Block::Water).count();
+            assert_eq!(water, 9);
+        }
+    }
+}
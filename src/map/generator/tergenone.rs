@@ -2,10 +2,10 @@
 
 use std::clone::Clone;
 
-use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
-use rand::random;
+use rand::{Rng, RngCore};
 
-use map::generator::Generator;
+use map::generator::noise_kind::Fractal;
+use map::generator::{seeded_rng, validate_dims, Generator, GeneratorError, NoiseKind};
 use map::{Block, IsoMap};
 
 /// A terrain generator which uses Perlin noise for heightmap generation.
@@ -20,11 +20,16 @@ use map::{Block, IsoMap};
 /// let gen = TerGenOne::new().set_len(32);
 /// let iso_map = gen.generate();
 /// ```
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TerGenOne {
-    /// Dimensions of the map
-    len: usize,
+    /// Dimensions of the map, along the x, y and z axes
+    dims: (usize, usize, usize),
     frequency: f64,
+    noise: NoiseKind,
+    octaves: Option<usize>,
+    persistence: Option<f64>,
+    lacunarity: Option<f64>,
+    seed: Option<u64>,
 }
 
 impl TerGenOne {
@@ -34,10 +39,24 @@ impl TerGenOne {
     /// Default frequency parameter for the noise generator
     const DEFAULT_FREQUENCY: f64 = 0.05;
 
-    /// Set the edge length
+    /// Set the edge length, making the map a cube
     pub fn set_len(self, len: usize) -> TerGenOne {
         // level 0 should end up bewtween 40% and 60%
-        TerGenOne { len, ..self }
+        TerGenOne {
+            dims: (len, len, len),
+            ..self
+        }
+    }
+
+    /// Set the lengths of the map along the x, y and z axes
+    ///
+    /// `z` is the height of the map, so wide, flat landscapes can be made with
+    /// a small `z`.
+    pub fn set_dims(self, x: usize, y: usize, z: usize) -> TerGenOne {
+        TerGenOne {
+            dims: (x, y, z),
+            ..self
+        }
     }
 
     /// Set the frequency parameter for the noise generator
@@ -52,11 +71,71 @@ impl TerGenOne {
         }
     }
 
+    /// Set the kind of noise used for the heightmap
+    ///
+    /// The default is [`NoiseKind::Fbm`](enum.NoiseKind.html#variant.Fbm).
+    /// The frequency means something different for every kind of noise, so it
+    /// usually needs adjusting along with this.
+    pub fn set_noise(self, noise: NoiseKind) -> TerGenOne {
+        TerGenOne { noise, ..self }
+    }
+
+    /// Set the number of octaves of the noise
+    ///
+    /// Every octave adds finer detail on top of the previous ones, so more
+    /// octaves make rougher terrain, at the cost of generation time. If unset,
+    /// the noise function's default is used, which is 6 for all of them.
+    pub fn set_octaves(self, octaves: usize) -> TerGenOne {
+        TerGenOne {
+            octaves: Some(octaves),
+            ..self
+        }
+    }
+
+    /// Set the persistence of the noise
+    ///
+    /// This is how much each octave contributes compared to the previous one.
+    /// Higher values make the fine detail more pronounced. If unset, the noise
+    /// function's default is used.
+    pub fn set_persistence(self, persistence: f64) -> TerGenOne {
+        TerGenOne {
+            persistence: Some(persistence),
+            ..self
+        }
+    }
+
+    /// Set the lacunarity of the noise
+    ///
+    /// This is how much the frequency grows with each octave. If unset, the
+    /// noise function's default is used.
+    pub fn set_lacunarity(self, lacunarity: f64) -> TerGenOne {
+        TerGenOne {
+            lacunarity: Some(lacunarity),
+            ..self
+        }
+    }
+
+    /// Set the seed for the random number generator
+    ///
+    /// Generators with the same seed and parameters will always produce the
+    /// same map. Without a seed, every map is different.
+    pub fn set_seed(self, seed: u64) -> TerGenOne {
+        TerGenOne {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
     /// Get a new terrain generator with all default settings
     pub fn new() -> TerGenOne {
         TerGenOne {
-            len: Self::DEFAULT_LEN,
+            dims: (Self::DEFAULT_LEN, Self::DEFAULT_LEN, Self::DEFAULT_LEN),
             frequency: Self::DEFAULT_FREQUENCY,
+            noise: NoiseKind::Fbm,
+            octaves: None,
+            persistence: None,
+            lacunarity: None,
+            seed: None,
         }
     }
 
@@ -68,45 +147,107 @@ impl TerGenOne {
     /// which show even those blocks that are obscured in the final render. This
     /// can be useful for testing or diagnostics.
     pub fn generate_slices(&self) -> Vec<IsoMap> {
-        let noise = Fbm::new().set_seed(random()).set_frequency(self.frequency);
-        let mut isomap = IsoMap::new_empty(self.len);
-        let half_height: f64 = self.len as f64 / 2.0;
         let mut maps: Vec<IsoMap> = Vec::new();
 
-        for x in 0..isomap.len() {
-            for y in 0..isomap.len() {
+        self.generate_with(&mut seeded_rng(self.seed), |isomap| {
+            maps.push(isomap.clone())
+        });
+
+        maps
+    }
+
+    /// Generate a map using `rng`, calling `on_slice` with the map so far each
+    /// time one slice in the x-axis is added
+    fn generate_with<F: FnMut(&IsoMap)>(&self, rng: &mut dyn RngCore, mut on_slice: F) -> IsoMap {
+        let noise = self.noise.build(rng.gen(), &self.fractal());
+        let (len_x, len_y, len_z) = self.dims;
+        let mut isomap = IsoMap::new_empty_dims(len_x, len_y, len_z);
+        let half_height: f64 = len_z as f64 / 2.0;
+
+        for x in 0..len_x {
+            for y in 0..len_y {
                 let height =
                     (half_height + ((noise.get([x as f64, y as f64])) * half_height)) as usize;
+                let height = height.min(len_z);
 
                 let mut column = isomap.0.slice_mut(s![x, y, 0..height]);
 
                 column.fill(Block::Rock);
             }
 
-            maps.push(isomap.clone());
+            on_slice(&isomap);
         }
 
-        maps
+        isomap
+    }
+}
+
+impl TerGenOne {
+    /// Get the settings for the heightmap noise
+    fn fractal(&self) -> Fractal {
+        Fractal {
+            frequency: self.frequency,
+            octaves: self.octaves,
+            persistence: self.persistence,
+            lacunarity: self.lacunarity,
+        }
+    }
+}
+
+impl Default for TerGenOne {
+    fn default() -> TerGenOne {
+        TerGenOne::new()
     }
 }
 
 impl Generator for TerGenOne {
     fn generate(&self) -> IsoMap {
-        let noise = Fbm::new().set_seed(random()).set_frequency(self.frequency);
-        let mut isomap = IsoMap::new_empty(self.len);
-        let half_height: f64 = self.len as f64 / 2.0;
+        self.generate_with_rng(&mut seeded_rng(self.seed))
+    }
 
-        for x in 0..isomap.len() {
-            for y in 0..isomap.len() {
-                let height =
-                    (half_height + ((noise.get([x as f64, y as f64])) * half_height)) as usize;
+    fn validate(&self) -> Result<(), GeneratorError> {
+        validate_dims(self.dims)
+    }
 
-                let mut column = isomap.0.slice_mut(s![x, y, 0..height]);
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap {
+        self.generate_with(rng, |_| ())
+    }
 
-                column.fill(Block::Rock);
-            }
-        }
+    fn generate_with_progress(&self, progress: &mut dyn FnMut(f32)) -> IsoMap {
+        let len_x = self.dims.0;
+        let mut slices = 0;
 
-        isomap
+        self.generate_with(&mut seeded_rng(self.seed), |_| {
+            slices += 1;
+            progress(slices as f32 / len_x as f32);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_map() {
+        let gen = TerGenOne::new().set_len(16).set_seed(1234);
+
+        assert_eq!(gen.generate().0, gen.generate().0);
+    }
+
+    #[test]
+    fn zero_dimension() {
+        assert_eq!(
+            TerGenOne::new().set_dims(4, 0, 4).try_generate().err(),
+            Some(GeneratorError::ZeroDimension)
+        );
+        assert!(TerGenOne::new().set_len(4).try_generate().is_ok());
+    }
+
+    #[test]
+    fn rectangular_map() {
+        let map = TerGenOne::new().set_dims(16, 8, 4).generate();
+
+        assert_eq!(map.0.shape(), &[16, 8, 4]);
     }
 }
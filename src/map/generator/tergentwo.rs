@@ -1,9 +1,17 @@
 //! A terrain generator for making generic landscapes
 
-use noise::{Billow, Abs, Fbm, MultiFractal, NoiseFn, Seedable};
-use rand::{thread_rng, Rng};
+use ndarray::{Array2, ArrayViewMut1};
+#[cfg(feature = "rayon")]
+use ndarray::{ArrayViewMut2, Axis};
+use noise::{Abs, Billow, MultiFractal, NoiseFn, Seedable};
+use rand::{thread_rng, Rng, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
-use map::generator::Generator;
+use map::generator::rivers::{carve_channel, river_columns};
+use map::generator::noise_kind::Fractal;
+use map::generator::water::remove_small_water;
+use map::generator::{heightmap, seeded_rng, validate_dims, Generator, GeneratorError, NoiseKind};
 use map::{Block, IsoMap};
 
 /// A terrain generator which uses Perlin noise for heightmap generation.
@@ -18,13 +26,23 @@ use map::{Block, IsoMap};
 /// let gen = TerGenTwo::new().set_len(128);
 /// let iso_map = gen.generate();
 /// ```
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TerGenTwo {
-    len: usize,
+    dims: (usize, usize, usize),
     frequency: f64,
+    noise: NoiseKind,
+    octaves: Option<usize>,
+    persistence: Option<f64>,
+    lacunarity: Option<f64>,
     layer_height: usize,
     min_soil_cutoff: usize,
     max_water_level: usize,
+    river_threshold: Option<usize>,
+    beach_width: usize,
+    snow_line: usize,
+    falloff: f64,
+    min_water_area: usize,
+    seed: Option<u64>,
 }
 
 impl TerGenTwo {
@@ -33,10 +51,32 @@ impl TerGenTwo {
     const DEFAULT_LAYER_HEIGHT: usize = 15;
     const DEFAULT_MIN_SOIL_CUTOFF: usize = 45;
     const DEFAULT_MAX_WATER_LEVEL: usize = 40;
+    const DEFAULT_BEACH_WIDTH: usize = 0;
+    const DEFAULT_SNOW_LINE: usize = usize::MAX;
+    const DEFAULT_FALLOFF: f64 = 0.0;
+    const DEFAULT_MIN_WATER_AREA: usize = 0;
 
-    /// Set the edge length
+    /// Minimum number of soil blocks under a grass or sand cap
+    const MIN_SOIL_DEPTH: usize = 1;
+
+    /// Set the edge length, making the map a cube
     pub fn set_len(self, len: usize) -> TerGenTwo {
-        TerGenTwo { len, ..self }
+        TerGenTwo {
+            dims: (len, len, len),
+            ..self
+        }
+    }
+
+    /// Set the lengths of the map along the x, y and z axes
+    ///
+    /// `z` is the height of the map, so wide, flat landscapes can be made with
+    /// a small `z`. The water level and soil cutoff are measured along the z
+    /// axis, so they should be adjusted to fit.
+    pub fn set_dims(self, x: usize, y: usize, z: usize) -> TerGenTwo {
+        TerGenTwo {
+            dims: (x, y, z),
+            ..self
+        }
     }
 
     /// Set the frequency parameter for the noise generator
@@ -55,7 +95,8 @@ impl TerGenTwo {
     ///
     /// The layer height determines how deep the soil layer will be. The actual
     /// layer height is subject to a noise function, so this is the maximum that
-    /// it can actually be
+    /// it can actually be. Wherever there is a layer at all, it's at least two
+    /// blocks deep, so that the grass on top always has soil under it.
     pub fn set_layer_height(self, layer_height: usize) -> TerGenTwo {
         TerGenTwo {
             layer_height,
@@ -88,78 +129,833 @@ impl TerGenTwo {
         }
     }
 
+    /// Set the river threshold
+    ///
+    /// Rivers are carved where the water draining down the terrain collects.
+    /// Every column drains into its steepest downhill neighbour, and a column
+    /// becomes part of a river once at least `river_threshold` columns drain
+    /// through it. Lower values produce more, and more branching, rivers. By
+    /// default, no rivers are carved.
+    pub fn set_river_threshold(self, river_threshold: usize) -> TerGenTwo {
+        TerGenTwo {
+            river_threshold: Some(river_threshold),
+            ..self
+        }
+    }
+
+    /// Set the beach width
+    ///
+    /// Terrain which rises less than `beach_width` blocks above the water
+    /// level will be topped with sand instead of grass. By default, there are
+    /// no beaches.
+    pub fn set_beach_width(self, beach_width: usize) -> TerGenTwo {
+        TerGenTwo {
+            beach_width,
+            ..self
+        }
+    }
+
+    /// Set the snow line
+    ///
+    /// Columns rising above the snow line are topped with snow instead of
+    /// grass, sand or bare rock. By default, the snow line is above any map,
+    /// so there is no snow.
+    pub fn set_snow_line(self, snow_line: usize) -> TerGenTwo {
+        TerGenTwo { snow_line, ..self }
+    }
+
+    /// Set how strongly the terrain sinks towards the edges of the map
+    ///
+    /// The height of every column is multiplied by `1 - falloff * d²`, where
+    /// `d` is the distance from the centre of the map, scaled so that it's 1
+    /// in the middle of each edge. Heights can't go below 0, and at a falloff
+    /// of 1 or more, the edges sink all the way down, leaving an island
+    /// surrounded by water as long as the water level is above 0. The shape
+    /// of the island follows the shape of the map, so it's round on square
+    /// maps. The default of 0 leaves the terrain as is, and so do negative
+    /// values.
+    pub fn set_falloff(self, falloff: f64) -> TerGenTwo {
+        TerGenTwo { falloff, ..self }
+    }
+
+    /// Set the smallest area a body of water can have
+    ///
+    /// Small dips in the terrain below the water level make puddles, which
+    /// can look like noise. After the map is generated, the water on every
+    /// floor is split into bodies of connected blocks, and those with fewer
+    /// than `min_water_area` blocks are filled in with the ground below them.
+    /// Rivers are left alone. By default, no water is removed.
+    pub fn set_min_water_area(self, min_water_area: usize) -> TerGenTwo {
+        TerGenTwo {
+            min_water_area,
+            ..self
+        }
+    }
+
+    /// Set the kind of noise used for the heightmap
+    ///
+    /// The default is [`NoiseKind::Fbm`](enum.NoiseKind.html#variant.Fbm).
+    /// The frequency means something different for every kind of noise, so it
+    /// usually needs adjusting along with this.
+    pub fn set_noise(self, noise: NoiseKind) -> TerGenTwo {
+        TerGenTwo { noise, ..self }
+    }
+
+    /// Set the number of octaves of the noise
+    ///
+    /// Every octave adds finer detail on top of the previous ones, so more
+    /// octaves make rougher terrain, at the cost of generation time. If unset,
+    /// the noise function's default is used, which is 6 for all of them.
+    pub fn set_octaves(self, octaves: usize) -> TerGenTwo {
+        TerGenTwo {
+            octaves: Some(octaves),
+            ..self
+        }
+    }
+
+    /// Set the persistence of the noise
+    ///
+    /// This is how much each octave contributes compared to the previous one.
+    /// Higher values make the fine detail more pronounced. If unset, the noise
+    /// function's default is used.
+    pub fn set_persistence(self, persistence: f64) -> TerGenTwo {
+        TerGenTwo {
+            persistence: Some(persistence),
+            ..self
+        }
+    }
+
+    /// Set the lacunarity of the noise
+    ///
+    /// This is how much the frequency grows with each octave. If unset, the
+    /// noise function's default is used.
+    pub fn set_lacunarity(self, lacunarity: f64) -> TerGenTwo {
+        TerGenTwo {
+            lacunarity: Some(lacunarity),
+            ..self
+        }
+    }
+
+    /// Set the seed for the random number generator
+    ///
+    /// Generators with the same seed and parameters will always produce the
+    /// same map. Without a seed, every map is different.
+    pub fn set_seed(self, seed: u64) -> TerGenTwo {
+        TerGenTwo {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
     /// Get a new terrain generator with all default settings
     pub fn new() -> TerGenTwo {
         TerGenTwo {
-            len: Self::DEFAULT_LEN,
+            dims: (Self::DEFAULT_LEN, Self::DEFAULT_LEN, Self::DEFAULT_LEN),
             frequency: Self::DEFAULT_FREQUENCY,
+            noise: NoiseKind::Fbm,
+            octaves: None,
+            persistence: None,
+            lacunarity: None,
             layer_height: Self::DEFAULT_LAYER_HEIGHT,
             min_soil_cutoff: Self::DEFAULT_MIN_SOIL_CUTOFF,
             max_water_level: Self::DEFAULT_MAX_WATER_LEVEL,
+            river_threshold: None,
+            beach_width: Self::DEFAULT_BEACH_WIDTH,
+            snow_line: Self::DEFAULT_SNOW_LINE,
+            falloff: Self::DEFAULT_FALLOFF,
+            min_water_area: Self::DEFAULT_MIN_WATER_AREA,
+            seed: None,
         }
     }
 }
 
-impl Generator for TerGenTwo {
-    fn generate(&self) -> IsoMap {
-        let mut rng = thread_rng();
+impl TerGenTwo {
+    /// Generate a map, returning the seed it was generated from along with it
+    ///
+    /// Without a seed set, a new one is picked at random, so a map which turns
+    /// out well can be made again by passing the seed to
+    /// [`set_seed`](#method.set_seed). With a seed set, that seed is used and
+    /// returned.
+    pub fn generate_with_reported_seed(&self) -> (IsoMap, u64) {
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+
+        (self.generate_with_rng(&mut seeded_rng(Some(seed))), seed)
+    }
+
+    /// Find out the water and soil levels of a map without generating it
+    ///
+    /// These are picked at random for every map, within the limits set with
+    /// [`set_max_water_level`](#method.set_max_water_level) and
+    /// [`set_min_soil_cutoff`](#method.set_min_soil_cutoff). Without a seed
+    /// set, a new one is picked at random, and passing the returned seed to
+    /// [`set_seed`](#method.set_seed) makes a generator for the map described.
+    pub fn dry_run(&self) -> GenerationInfo {
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+        let (_, _, water_level, soil_level) = self.roll(&mut seeded_rng(Some(seed)));
+
+        GenerationInfo {
+            seed,
+            water_level,
+            soil_level,
+        }
+    }
+
+    /// Generate a map, creating a snapshot each time one slice in the x-axis is
+    /// added.
+    ///
+    /// The snapshots can be fed to the renderer to generate a series of images
+    /// which show even those blocks that are obscured in the final render. This
+    /// can be useful for testing or diagnostics. With a seed set, the last
+    /// snapshot is the same map that [`generate`](#method.generate) produces.
+    pub fn generate_slices(&self) -> Vec<IsoMap> {
+        let mut maps: Vec<IsoMap> = Vec::new();
+
+        self.generate_with(&mut seeded_rng(self.seed), |isomap| {
+            maps.push(isomap.clone())
+        });
+
+        maps
+    }
+
+    /// Generate a map using multiple threads
+    ///
+    /// The map is identical to the one [`generate`](#method.generate) makes,
+    /// since everything random about it is picked before the columns are
+    /// filled in, and then every thread fills in its own slices along the x
+    /// axis. Only available with the `rayon` feature.
+    #[cfg(feature = "rayon")]
+    pub fn generate_parallel(&self) -> IsoMap {
+        let plan = self.plan(&mut seeded_rng(self.seed));
+        let (len_x, len_y, len_z) = self.dims;
+        let mut isomap = IsoMap::new_empty_dims(len_x, len_y, len_z);
+
+        let slices: Vec<ArrayViewMut2<Block>> = isomap.0.axis_iter_mut(Axis(0)).collect();
+        slices.into_par_iter().enumerate().for_each(|(x, mut slice)| {
+            for (y, column) in slice.axis_iter_mut(Axis(0)).enumerate() {
+                self.fill_column(column, x, y, &plan);
+            }
+        });
+
+        self.clean_up_water(&mut isomap, &plan);
+
+        isomap
+    }
+
+    /// Generate a map using `rng`, calling `on_slice` with the map so far each
+    /// time one slice in the x-axis is added
+    fn generate_with<F: FnMut(&IsoMap)>(&self, rng: &mut dyn RngCore, mut on_slice: F) -> IsoMap {
+        let plan = self.plan(rng);
+        let (len_x, len_y, len_z) = self.dims;
+        let mut isomap = IsoMap::new_empty_dims(len_x, len_y, len_z);
+
+        for x in 0..len_x {
+            for y in 0..len_y {
+                self.fill_column(isomap.0.slice_mut(s![x, y, ..]), x, y, &plan);
+            }
+
+            // The last slice is the finished map
+            if x + 1 == len_x {
+                self.clean_up_water(&mut isomap, &plan);
+            }
+
+            on_slice(&isomap);
+        }
+
+        isomap
+    }
+
+    /// Remove the bodies of water in `isomap` which are smaller than the
+    /// minimum water area
+    fn clean_up_water(&self, isomap: &mut IsoMap, plan: &Plan) {
+        if self.min_water_area == 0 {
+            return;
+        }
 
-        let height_noise = Fbm::new().set_seed(rng.gen()).set_frequency(self.frequency);
-        let billow = Billow::new()
-            .set_seed(rng.gen())
+        // Rivers are carved at most one floor below the top floor of the
+        // water, so stopping below that leaves them alone
+        let levels = 0..plan.water_level.saturating_sub(2);
+        remove_small_water(isomap, self.min_water_area, levels);
+    }
+
+    /// Pick everything random about a map using `rng`
+    fn plan(&self, rng: &mut dyn RngCore) -> Plan {
+        let (height_seed, layer_seed, water_level, soil_level) = self.roll(rng);
+
+        let height_noise = self.noise.build(height_seed, &self.fractal());
+        let layer_noise = Billow::new()
+            .set_seed(layer_seed)
             .set_frequency(self.frequency);
 
-        // Billow returns negative values 
-        let layer_noise = Abs::new(&billow);
+        let mut heights = heightmap(&*height_noise, self.dims);
+        if self.falloff > 0.0 {
+            apply_falloff(&mut heights, self.falloff);
+        }
+
+        let rivers = self
+            .river_threshold
+            .map(|threshold| river_columns(&heights, threshold));
+
+        Plan {
+            layer_noise,
+            water_level,
+            soil_level,
+            heights,
+            rivers,
+        }
+    }
 
+    /// Pick the random numbers a map is made from using `rng`, which are the
+    /// seeds for the height and layer noise, the water level and the soil
+    /// level, in that order
+    fn roll(&self, rng: &mut dyn RngCore) -> (u32, u32, usize, usize) {
+        let height_seed: u32 = rng.gen();
+        let layer_seed: u32 = rng.gen();
         let water_level: usize = rng.gen_range(0, self.max_water_level + 1);
-        let soil_level: usize = rng.gen_range(self.min_soil_cutoff, self.len);
-
-        let mut isomap = IsoMap::new_empty(self.len);
-        let half_height: f64 = self.len as f64 / 2.0;
-
-        for x in 0..isomap.len() {
-            for y in 0..isomap.len() {
-                let height = (half_height
-                    + ((height_noise.get([x as f64, y as f64])) * half_height))
-                    as usize;
-
-                if height < water_level {
-                    // Rock, and then water up to the water level
-                    isomap.0.slice_mut(s![x, y, 0..height-1]).fill(Block::Rock);
-                    isomap
-                        .0
-                        .slice_mut(s![x, y, height-1..water_level-1])
-                        .fill(Block::Water);
-                } else if height < soil_level {
-                    // Rock, and then soil, then a single block of grass
-                    let soil_depth =
-                        (layer_noise.get([x as f64, y as f64]) * self.layer_height as f64) as usize;
-
-                    let rock_height: usize = height.saturating_sub(soil_depth);
-
-                    isomap
-                        .0
-                        .slice_mut(s![x, y, 0..rock_height])
-                        .fill(Block::Rock);
-
-                    if rock_height < height-1 {
-                        isomap
-                            .0
-                            .slice_mut(s![x, y, rock_height..(height - 1)])
-                            .fill(Block::Soil);
-                    } 
-                    
-                    if rock_height < height {
-                        isomap.0[[x, y, height-1]] = Block::Grass;
+        let soil_level: usize = rng.gen_range(self.min_soil_cutoff, self.dims.2);
+
+        (height_seed, layer_seed, water_level, soil_level)
+    }
+
+    /// Fill in `column`, the column at `x`, `y`, following `plan`
+    fn fill_column(&self, mut column: ArrayViewMut1<Block>, x: usize, y: usize, plan: &Plan) {
+        let height = plan.heights[[x, y]];
+        let water_level = plan.water_level;
+
+        // Billow returns negative values 
+        let layer_noise = Abs::new(&plan.layer_noise);
+
+        if height < water_level {
+            // Rock, and then water up to the water level, which never
+            // reaches the top floor. Even the lowest columns keep a block
+            // of rock at the bottom as a seabed
+            let len = column.len();
+            let rock_top = height.saturating_sub(1).max(1).min(len);
+            let water_top = (water_level - 1).min(len - 1).max(rock_top);
+
+            column.slice_mut(s![0..rock_top]).fill(Block::Rock);
+            column
+                .slice_mut(s![rock_top..water_top])
+                .fill(Block::Water);
+        } else if height < plan.soil_level {
+            // Rock, and then soil, then a single block of grass, or
+            // sand if we're close enough to the water
+            let soil_depth =
+                (layer_noise.get([x as f64, y as f64]) * self.layer_height as f64) as usize;
+
+            // The cap always sits on soil, so a layer that's there at
+            // all is deep enough for both
+            let soil_depth = match soil_depth {
+                0 => 0,
+                depth => depth.max(Self::MIN_SOIL_DEPTH + 1),
+            };
+
+            let rock_height: usize = height.saturating_sub(soil_depth);
+
+            column.slice_mut(s![0..rock_height]).fill(Block::Rock);
+
+            if rock_height + 1 < height {
+                column
+                    .slice_mut(s![rock_height..(height - 1)])
+                    .fill(Block::Soil);
+            } 
+            
+            if rock_height < height {
+                column[height-1] = if height - water_level < self.beach_width {
+                    Block::Sand
+                } else {
+                    Block::Grass
+                };
+            }
+        } else {
+            // Just rock
+            column.slice_mut(s![0..height]).fill(Block::Rock);
+        }
+
+        // Peaks get a cap of snow, which rivers can still cut through
+        if height > self.snow_line && height >= water_level {
+            column[height - 1] = Block::Snow;
+        }
+
+        // Rivers cut a channel into the column, and fill it with water,
+        // unless the column is already underwater
+        if let Some(ref rivers) = plan.rivers {
+            if rivers[[x, y]] && height > 0 && height >= water_level {
+                carve_channel(column, height);
+            }
+        }
+    }
+}
+
+/// What was picked at random for a map made by `TerGenTwo`, as returned by
+/// [`TerGenTwo::dry_run`](struct.TerGenTwo.html#method.dry_run)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GenerationInfo {
+    /// The seed the map is generated from
+    pub seed: u64,
+
+    /// The height water fills low ground up to, between 0 and the maximum
+    /// water level
+    pub water_level: usize,
+
+    /// The height above which there's only bare rock, between the minimum soil
+    /// cutoff and the height of the map
+    pub soil_level: usize,
+}
+
+/// Lower the columns of `heights` by their distance from the centre, as
+/// described in [`TerGenTwo::set_falloff`](struct.TerGenTwo.html#method.set_falloff)
+fn apply_falloff(heights: &mut Array2<usize>, falloff: f64) {
+    let (len_x, len_y) = heights.dim();
+    let (half_x, half_y) = (len_x as f64 / 2.0, len_y as f64 / 2.0);
+
+    for ((x, y), height) in heights.indexed_iter_mut() {
+        // Measured from the centres of the columns, so the falloff is
+        // symmetrical
+        let dx = (x as f64 + 0.5 - half_x) / half_x;
+        let dy = (y as f64 + 0.5 - half_y) / half_y;
+        let factor = (1.0 - falloff * (dx * dx + dy * dy)).max(0.0);
+
+        *height = (*height as f64 * factor) as usize;
+    }
+}
+
+/// Everything random about a map generated by `TerGenTwo`, picked before any
+/// of its columns are filled in
+struct Plan {
+    /// Noise for the depth of the soil layer, which has to be made absolute
+    layer_noise: Billow,
+    water_level: usize,
+    soil_level: usize,
+    heights: Array2<usize>,
+
+    /// Columns which are part of a river, if there are rivers
+    rivers: Option<Array2<bool>>,
+}
+
+impl TerGenTwo {
+    /// Get the settings for the heightmap noise
+    fn fractal(&self) -> Fractal {
+        Fractal {
+            frequency: self.frequency,
+            octaves: self.octaves,
+            persistence: self.persistence,
+            lacunarity: self.lacunarity,
+        }
+    }
+}
+
+impl Default for TerGenTwo {
+    fn default() -> TerGenTwo {
+        TerGenTwo::new()
+    }
+}
+
+impl Generator for TerGenTwo {
+    fn generate(&self) -> IsoMap {
+        self.generate_with_rng(&mut seeded_rng(self.seed))
+    }
+
+    fn validate(&self) -> Result<(), GeneratorError> {
+        validate_dims(self.dims)?;
+
+        let height = self.dims.2;
+
+        if self.min_soil_cutoff >= height {
+            return Err(GeneratorError::SoilCutoffTooHigh {
+                min_soil_cutoff: self.min_soil_cutoff,
+                height,
+            });
+        }
+
+        if self.max_water_level >= height {
+            return Err(GeneratorError::WaterLevelTooHigh {
+                max_water_level: self.max_water_level,
+                height,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn generate_with_rng(&self, rng: &mut dyn RngCore) -> IsoMap {
+        self.generate_with(rng, |_| ())
+    }
+
+    fn generate_with_progress(&self, progress: &mut dyn FnMut(f32)) -> IsoMap {
+        let len_x = self.dims.0;
+        let mut slices = 0;
+
+        self.generate_with(&mut seeded_rng(self.seed), |_| {
+            slices += 1;
+            progress(slices as f32 / len_x as f32);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_map() {
+        let gen = TerGenTwo::new().set_seed(1234);
+
+        assert_eq!(gen.generate().0, gen.generate().0);
+    }
+
+    #[test]
+    fn reported_seed_reproduces_map() {
+        let gen = TerGenTwo::new()
+            .set_len(16)
+            .set_min_soil_cutoff(8)
+            .set_max_water_level(6);
+        let (map, seed) = gen.generate_with_reported_seed();
+
+        assert_eq!(gen.set_seed(seed).generate().0, map.0);
+        assert_eq!(
+            TerGenTwo::new().set_seed(9).generate_with_reported_seed().1,
+            9
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_serial() {
+        let gen = TerGenTwo::new()
+            .set_dims(40, 24, 48)
+            .set_min_soil_cutoff(20)
+            .set_max_water_level(20)
+            .set_beach_width(2)
+            .set_snow_line(30)
+            .set_river_threshold(20)
+            .set_seed(8);
+
+        assert_eq!(gen.generate_parallel(), gen.generate());
+    }
+
+    #[test]
+    fn progress_per_slice() {
+        let gen = TerGenTwo::new().set_dims(12, 8, 16).set_min_soil_cutoff(10).set_seed(4);
+        let mut reports = Vec::new();
+        let map = gen.generate_with_progress(&mut |done| reports.push(done));
+
+        assert_eq!(map, gen.generate());
+        assert_eq!(reports.len(), 12);
+        assert_eq!(reports[0], 1.0 / 12.0);
+        assert_eq!(reports.last(), Some(&1.0));
+        assert!(reports.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn default_is_new() {
+        let iso_map = TerGenTwo::default().set_seed(1).generate();
+
+        assert_eq!(
+            iso_map.0.shape(),
+            TerGenTwo::new().set_seed(1).generate().0.shape()
+        );
+        assert!(iso_map.count_non_air() > 0);
+    }
+
+    #[test]
+    fn more_water_with_higher_level() {
+        // The water level is picked at random below the maximum, so we look at
+        // the total over many maps
+        let water = |max_water_level| -> usize {
+            (0..20)
+                .map(|seed| {
+                    TerGenTwo::new()
+                        .set_len(32)
+                        .set_min_soil_cutoff(10)
+                        .set_max_water_level(max_water_level)
+                        .set_seed(seed)
+                        .generate()
+                        .block_counts()[&Block::Water]
+                }).sum()
+        };
+
+        assert!(water(12) > water(4));
+    }
+
+    #[test]
+    fn dry_run_levels() {
+        for seed in 0..30 {
+            let gen = TerGenTwo::new()
+                .set_len(32)
+                .set_min_soil_cutoff(10)
+                .set_max_water_level(8)
+                .set_seed(seed);
+            let info = gen.dry_run();
+
+            assert_eq!(info.seed, seed);
+            assert!(info.water_level <= 8);
+            assert!(info.soil_level >= 10 && info.soil_level < 32);
+            assert_eq!(gen.plan(&mut seeded_rng(Some(seed))).water_level, info.water_level);
+        }
+
+        // An unseeded dry run reports the seed of the map it describes
+        let info = TerGenTwo::new().dry_run();
+        assert_eq!(TerGenTwo::new().set_seed(info.seed).dry_run(), info);
+    }
+
+    #[test]
+    fn island_falloff() {
+        let gen = || {
+            TerGenTwo::new()
+                .set_len(32)
+                .set_min_soil_cutoff(10)
+                .set_max_water_level(8)
+                .set_seed(9)
+        };
+        let solid = |map: &IsoMap| map.0.iter().filter(|block| block.is_solid()).count();
+
+        let flat = gen().generate();
+        let island = gen().set_falloff(1.0).generate();
+        assert!(solid(&island) < solid(&flat));
+
+        // The corners are further than the edges, so nothing is left of them
+        // but the seabed
+        for &(x, y) in &[(0, 0), (31, 0), (0, 31), (31, 31)] {
+            assert!(island.0.slice(s![x, y, 1..]).iter().all(|block| !block.is_solid()));
+        }
+    }
+
+    #[test]
+    fn small_water_removed() {
+        use map::generator::surface_height;
+
+        let gen = TerGenTwo::new()
+            .set_len(32)
+            .set_min_soil_cutoff(10)
+            .set_max_water_level(12)
+            .set_frequency(0.1)
+            .set_river_threshold(30)
+            .set_seed(1);
+        let map = gen.generate();
+        let gen = gen.set_min_water_area(20);
+        let cleaned = gen.generate();
+        let water = |map: &IsoMap| map.block_counts()[&Block::Water];
+
+        assert!(water(&cleaned) < water(&map));
+        assert_eq!(cleaned.0.shape(), map.0.shape());
+
+        // Puddles are filled in rather than drained, so no column gets lower
+        for x in 0..32 {
+            for y in 0..32 {
+                assert!(surface_height(&cleaned, x, y) >= surface_height(&map, x, y));
+            }
+        }
+
+        #[cfg(feature = "rayon")]
+        assert_eq!(gen.generate_parallel(), cleaned);
+    }
+
+    #[test]
+    fn rivers_are_carved() {
+        use map::generator::surface_height;
+
+        let gen = TerGenTwo::new()
+            .set_len(32)
+            .set_min_soil_cutoff(10)
+            .set_max_water_level(12)
+            .set_frequency(0.1)
+            .set_seed(1);
+        let plain = gen.generate();
+        let rivers = gen.set_river_threshold(10).generate();
+
+        let mut carved = 0;
+        for x in 0..32 {
+            for y in 0..32 {
+                let height = surface_height(&plain, x, y);
+                if plain.0[[x, y, height - 1]] == rivers.0[[x, y, height - 1]] {
+                    continue;
+                }
+
+                // River columns lose their top block, and the one below it
+                // turns to water
+                assert_eq!(surface_height(&rivers, x, y), height - 1);
+                assert_eq!(rivers.0[[x, y, height - 2]], Block::Water);
+                carved += 1;
+            }
+        }
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn grass_sits_on_soil() {
+        use ndarray::Axis;
+
+        for seed in 0..10 {
+            let map = TerGenTwo::new()
+                .set_len(32)
+                .set_min_soil_cutoff(10)
+                .set_max_water_level(12)
+                .set_layer_height(3)
+                .set_beach_width(2)
+                .set_seed(seed)
+                .generate();
+
+            for column in map.0.lanes(Axis(2)) {
+                let column: Vec<Block> = column.iter().cloned().collect();
+
+                // Rock, then soil, then a single cap, then water and air
+                let rock = column.iter().take_while(|&&b| b == Block::Rock).count();
+                let soil = column[rock..].iter().take_while(|&&b| b == Block::Soil).count();
+                let rest = &column[rock + soil..];
+
+                match rest.first() {
+                    Some(&Block::Grass) | Some(&Block::Sand) => {
+                        assert!(soil >= TerGenTwo::MIN_SOIL_DEPTH || rock + soil == 0)
                     }
+                    _ => assert_eq!(soil, 0),
+                }
+
+                let above_cap = if soil > 0 { &rest[1..] } else { rest };
+                assert!(above_cap
+                    .iter()
+                    .all(|&b| b == Block::Water || b == Block::Air));
+            }
+        }
+    }
+
+    #[test]
+    fn snow_above_snow_line() {
+        use map::generator::surface_height;
+
+        let gen = TerGenTwo::new()
+            .set_len(32)
+            .set_min_soil_cutoff(10)
+            .set_max_water_level(12)
+            .set_seed(4);
+        assert_eq!(gen.generate().block_counts()[&Block::Snow], 0);
+
+        let map = gen.set_snow_line(18).generate();
+        let mut peaks = 0;
+
+        for x in 0..32 {
+            for y in 0..32 {
+                let height = surface_height(&map, x, y);
+                let column = map.0.slice(s![x, y, ..]);
+                let snow = column.iter().filter(|&&b| b == Block::Snow).count();
+
+                if height > 18 {
+                    assert_eq!(map.0[[x, y, height - 1]], Block::Snow);
+                    assert_eq!(snow, 1);
+                    peaks += 1;
                 } else {
-                    // Just rock
-                    isomap.0.slice_mut(s![x, y, 0..height]).fill(Block::Rock);
+                    assert_eq!(snow, 0);
                 }
             }
         }
 
-        isomap
+        assert!(peaks > 0);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert_eq!(
+            TerGenTwo::new().set_len(0).try_generate().err(),
+            Some(GeneratorError::ZeroDimension)
+        );
+        assert_eq!(
+            TerGenTwo::new().set_len(32).try_generate().err(),
+            Some(GeneratorError::SoilCutoffTooHigh {
+                min_soil_cutoff: 45,
+                height: 32,
+            })
+        );
+        assert_eq!(
+            TerGenTwo::new()
+                .set_len(32)
+                .set_min_soil_cutoff(10)
+                .try_generate()
+                .err(),
+            Some(GeneratorError::WaterLevelTooHigh {
+                max_water_level: 40,
+                height: 32,
+            })
+        );
+        assert!(TerGenTwo::new()
+            .set_len(16)
+            .set_min_soil_cutoff(8)
+            .set_max_water_level(4)
+            .try_generate()
+            .is_ok());
+    }
+
+    #[test]
+    fn same_rng_same_map() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let gen = TerGenTwo::new();
+        let first = gen.generate_with_rng(&mut StdRng::seed_from_u64(99));
+        let second = gen.generate_with_rng(&mut StdRng::seed_from_u64(99));
+
+        assert_eq!(first.0, second.0);
+    }
+
+    #[test]
+    fn last_slice_is_map() {
+        let gen = TerGenTwo::new().set_seed(1234);
+        let slices = gen.generate_slices();
+
+        assert_eq!(slices.len(), gen.dims.0);
+        assert_eq!(slices.last().unwrap().0, gen.generate().0);
+    }
+
+    #[test]
+    fn rectangular_map() {
+        let map = TerGenTwo::new()
+            .set_dims(32, 16, 8)
+            .set_max_water_level(3)
+            .set_min_soil_cutoff(4)
+            .generate();
+
+        assert_eq!(map.0.shape(), &[32, 16, 8]);
+    }
+
+    #[test]
+    fn deep_water_fill() {
+        use map::Block::{Air, Rock, Water};
+        use ndarray::Array1;
+
+        let fill = |height: usize, water_level: usize| -> Vec<Block> {
+            let gen = TerGenTwo::new().set_dims(1, 1, 6);
+            let plan = Plan {
+                layer_noise: Billow::new(),
+                water_level,
+                soil_level: 6,
+                heights: Array2::from_elem((1, 1), height),
+                rivers: None,
+            };
+            let mut column = Array1::from_elem(6, Block::Air);
+
+            gen.fill_column(column.view_mut(), 0, 0, &plan);
+            column.to_vec()
+        };
+
+        // Water up to the full height of the map still leaves the top floor
+        assert_eq!(fill(3, 6), vec![Rock, Rock, Water, Water, Water, Air]);
+        assert_eq!(fill(0, 6), vec![Rock, Water, Water, Water, Water, Air]);
+
+        // The lowest columns get a seabed rather than water all the way down
+        assert_eq!(fill(1, 4), vec![Rock, Water, Water, Air, Air, Air]);
+        assert_eq!(fill(1, 2), vec![Rock, Air, Air, Air, Air, Air]);
+    }
+
+    #[test]
+    fn zero_height_columns() {
+        // Tiny maps with a high frequency and low water produce columns with a
+        // height of 0, which used to underflow
+        for seed in 0..50 {
+            TerGenTwo::new()
+                .set_len(4)
+                .set_frequency(0.5)
+                .set_max_water_level(1)
+                .set_min_soil_cutoff(0)
+                .set_seed(seed)
+                .generate();
+        }
     }
 }
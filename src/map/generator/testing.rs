@@ -3,21 +3,42 @@
 //! This is not a unit/integration test, but a simple generator, which can be
 //! used in tests.
 
-use map::generator::Generator;
+use rand::RngCore;
+
+use map::generator::{Generator, GeneratorError};
 use map::{Block, IsoMap};
 
 /// a simple generator that produces a mostly flat map.
 ///
-/// The minimum size is 6. `dim` lower than that will peg the size to 6.
+/// The minimum size is 6. `dim` lower than that will peg the size to 6, or
+/// make [`try_generate`](trait.Generator.html#method.try_generate) return an
+/// error.
 #[derive(Debug)]
 pub struct TestingGenerator {
     /// Map dimension to use
     pub dim: usize,
 }
 
+impl TestingGenerator {
+    /// Smallest map the generator can make
+    const MIN_DIM: usize = 6;
+}
+
 impl Generator for TestingGenerator {
-    fn generate(&self) -> IsoMap {
-        let dim = if self.dim >= 6 { self.dim } else { 6 };
+    fn validate(&self) -> Result<(), GeneratorError> {
+        if self.dim < Self::MIN_DIM {
+            return Err(GeneratorError::TooSmall {
+                len: self.dim,
+                min: Self::MIN_DIM,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The testing map has no randomness, so `rng` is unused
+    fn generate_with_rng(&self, _rng: &mut dyn RngCore) -> IsoMap {
+        let dim = self.dim.max(Self::MIN_DIM);
 
         let mut new_map = IsoMap::new_empty(dim);
         let halfway = (dim as f64 / 2.0).floor() as usize;
@@ -50,6 +71,15 @@ mod tests {
         assert_eq!(map.0.shape(), &[6, 6, 6])
     }
 
+    #[test]
+    fn too_small_is_error() {
+        assert_eq!(
+            TestingGenerator { dim: 5 }.try_generate().err(),
+            Some(GeneratorError::TooSmall { len: 5, min: 6 })
+        );
+        assert!(TestingGenerator { dim: 6 }.try_generate().is_ok());
+    }
+
     #[test]
     fn gen_map() {
         let map = TestingGenerator { dim: 1 }.generate();
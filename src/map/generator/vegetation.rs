@@ -0,0 +1,157 @@
+//! Decorating already generated maps with trees and other small structures
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use map::generator::surface_height;
+use map::{Block, IsoMap};
+
+/// Height of the trunk of the tree used by
+/// [`scatter_trees`](fn.scatter_trees.html)
+const TRUNK_HEIGHT: usize = 2;
+
+/// Build a small tree, made of a `Soil` trunk topped with a `Grass` canopy
+///
+/// The tree is 3×3 columns wide, with the trunk in the middle column, so it
+/// should be placed with [`scatter_structures`](fn.scatter_structures.html).
+pub fn tree() -> IsoMap {
+    let mut tree = IsoMap::new_empty_dims(3, 3, TRUNK_HEIGHT + 2);
+
+    tree.0.slice_mut(s![1, 1, ..TRUNK_HEIGHT]).fill(Block::Soil);
+    tree.0.slice_mut(s![.., .., TRUNK_HEIGHT]).fill(Block::Grass);
+    tree.0[[1, 1, TRUNK_HEIGHT + 1]] = Block::Grass;
+
+    tree
+}
+
+/// Scatter trees over the grass in `map`
+///
+/// Every suitable column gets a tree with a probability of `density`, with the
+/// choices made by an RNG seeded by `seed`. See
+/// [`scatter_structures`](fn.scatter_structures.html) for which columns are
+/// suitable.
+pub fn scatter_trees(map: &mut IsoMap, density: f64, seed: u64) {
+    scatter_structures(map, &tree(), density, seed);
+}
+
+/// Scatter copies of `structure` over the grass in `map`
+///
+/// Every suitable column gets a copy with a probability of `density`, which
+/// must be between 0 and 1, with the choices made by an RNG seeded by `seed`.
+/// The middle column of `structure` is placed on top of the chosen column,
+/// and anything which doesn't fit in the map is clipped.
+///
+/// A column is suitable if its top block is grass, it's no more than one block
+/// higher or lower than any of its neighbours, and it isn't at or below the
+/// surface of any neighbouring water. Columns are picked before anything is
+/// placed, so structures don't affect each other's placement, although they
+/// may overlap.
+pub fn scatter_structures(map: &mut IsoMap, structure: &IsoMap, density: f64, seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (len_x, len_y, _) = map.shape();
+    let mut sites = Vec::new();
+
+    for x in 0..len_x {
+        for y in 0..len_y {
+            if is_suitable(map, x, y) && rng.gen_bool(density) {
+                sites.push((x, y, surface_height(map, x, y)));
+            }
+        }
+    }
+
+    let (centre_x, centre_y) = (structure.dim_x() / 2, structure.dim_y() / 2);
+
+    for (x, y, z) in sites {
+        // Structures hanging over the low edges of the map are clipped by
+        // slicing them, since the offset can't be negative
+        let clip_x = centre_x.saturating_sub(x);
+        let clip_y = centre_y.saturating_sub(y);
+        let clipped = structure.slice_region(
+            clip_x..structure.dim_x(),
+            clip_y..structure.dim_y(),
+            0..structure.dim_z(),
+        );
+
+        map.overlay(&clipped, (x + clip_x - centre_x, y + clip_y - centre_y, z));
+    }
+}
+
+/// Check whether a structure can be placed on the column at `x`, `y`
+fn is_suitable(map: &IsoMap, x: usize, y: usize) -> bool {
+    let height = surface_height(map, x, y);
+    if height == 0 || map.0[[x, y, height - 1]] != Block::Grass {
+        return false;
+    }
+
+    let (len_x, len_y, _) = map.shape();
+    let neighbours = [
+        (x.wrapping_sub(1), y),
+        (x + 1, y),
+        (x, y.wrapping_sub(1)),
+        (x, y + 1),
+    ];
+
+    neighbours
+        .iter()
+        .filter(|&&(nx, ny)| nx < len_x && ny < len_y)
+        .all(|&(nx, ny)| {
+            let neighbour_height = surface_height(map, nx, ny);
+            let steep = neighbour_height > height + 1 || neighbour_height + 1 < height;
+            let flooded = neighbour_height >= height
+                && map.0[[nx, ny, neighbour_height - 1]] == Block::Water;
+
+            !steep && !flooded
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array3;
+
+    /// A 5×5 map with grass at height 2, water along x = 0 and a cliff along
+    /// x = 4
+    fn meadow() -> IsoMap {
+        let mut map = IsoMap::new_empty_dims(5, 5, 10);
+        map.0.slice_mut(s![.., .., ..2]).fill(Block::Soil);
+        map.0.slice_mut(s![.., .., 1]).fill(Block::Grass);
+        map.0.slice_mut(s![0, .., 1]).fill(Block::Water);
+        map.0.slice_mut(s![4, .., 2..5]).fill(Block::Rock);
+
+        map
+    }
+
+    #[test]
+    fn full_density_avoids_water_and_slopes() {
+        let mut map = meadow();
+        let post = IsoMap(Array3::from_elem((1, 1, 1), Block::Soil));
+        scatter_structures(&mut map, &post, 1.0, 0);
+
+        for y in 0..5 {
+            // next to the water, and next to the cliff
+            assert_eq!(map.get(1, y, 2), Some(Block::Air));
+            assert_eq!(map.get(3, y, 2), Some(Block::Air));
+            assert_eq!(map.get(2, y, 2), Some(Block::Soil));
+        }
+    }
+
+    #[test]
+    fn zero_density_changes_nothing() {
+        let mut map = meadow();
+        scatter_trees(&mut map, 0.0, 0);
+
+        assert_eq!(map.0, meadow().0);
+    }
+
+    #[test]
+    fn trees_stand_on_grass() {
+        let mut map = meadow();
+        scatter_trees(&mut map, 1.0, 0);
+
+        assert_eq!(map.get(2, 0, 2), Some(Block::Soil));
+        assert_eq!(map.get(2, 0, 3), Some(Block::Soil));
+        assert_eq!(map.get(2, 0, 5), Some(Block::Grass));
+        // the canopy hangs over the neighbouring columns
+        assert_eq!(map.get(1, 0, 4), Some(Block::Grass));
+    }
+}
@@ -0,0 +1,204 @@
+//! Filling maps with water after they have been generated
+
+use std::ops::Range;
+
+use map::{Block, IsoMap};
+
+/// Fill the air below `level` which is connected to the sides of the map with
+/// water
+///
+/// The water flows in from the four vertical sides of the map, and spreads
+/// from block to block through shared faces, so basins enclosed by higher
+/// ground stay dry, as do caves sealed off from the outside. As with the water
+/// level of [`TerGenTwo`](struct.TerGenTwo.html), `level` is the height of the
+/// water surface: only blocks with a z coordinate below it are filled.
+///
+/// This can be used in place of the water generated by `TerGenTwo`, by setting
+/// its maximum water level to 0.
+pub fn flood_fill_water(map: &mut IsoMap, level: usize) {
+    if map.0.is_empty() {
+        return;
+    }
+
+    let (len_x, len_y, len_z) = map.shape();
+    let level = level.min(len_z);
+    let mut stack = Vec::new();
+
+    for z in 0..level {
+        for x in 0..len_x {
+            stack.push((x, 0, z));
+            stack.push((x, len_y - 1, z));
+        }
+        for y in 0..len_y {
+            stack.push((0, y, z));
+            stack.push((len_x - 1, y, z));
+        }
+    }
+
+    while let Some((x, y, z)) = stack.pop() {
+        if map.0[[x, y, z]] != Block::Air {
+            continue;
+        }
+
+        map.0[[x, y, z]] = Block::Water;
+
+        if x > 0 {
+            stack.push((x - 1, y, z));
+        }
+        if x + 1 < len_x {
+            stack.push((x + 1, y, z));
+        }
+        if y > 0 {
+            stack.push((x, y - 1, z));
+        }
+        if y + 1 < len_y {
+            stack.push((x, y + 1, z));
+        }
+        if z > 0 {
+            stack.push((x, y, z - 1));
+        }
+        if z + 1 < level {
+            stack.push((x, y, z + 1));
+        }
+    }
+}
+
+/// Remove the bodies of water on floors `levels` which cover fewer than
+/// `min_area` blocks
+///
+/// Bodies of water are found separately on every floor, as water connected
+/// through the sides of blocks. Floors are cleaned up from the bottom up, and
+/// every block of a body which is too small becomes a copy of the solid block
+/// below it, filling the dip it was in, or air if there's nothing solid below.
+pub fn remove_small_water(map: &mut IsoMap, min_area: usize, levels: Range<usize>) {
+    let (len_x, len_y, len_z) = map.shape();
+    let levels = levels.start..levels.end.min(len_z);
+    let index = |x: usize, y: usize| x * len_y + y;
+    let mut seen = vec![false; len_x * len_y];
+    let mut body = Vec::new();
+    let mut stack = Vec::new();
+
+    for z in levels {
+        seen.fill(false);
+
+        for start_x in 0..len_x {
+            for start_y in 0..len_y {
+                if seen[index(start_x, start_y)] || map.0[[start_x, start_y, z]] != Block::Water {
+                    continue;
+                }
+
+                // Collect the whole body of water this block is part of
+                body.clear();
+                stack.push((start_x, start_y));
+                seen[index(start_x, start_y)] = true;
+
+                while let Some((x, y)) = stack.pop() {
+                    body.push((x, y));
+
+                    let mut visit = |x: usize, y: usize| {
+                        if !seen[index(x, y)] && map.0[[x, y, z]] == Block::Water {
+                            seen[index(x, y)] = true;
+                            stack.push((x, y));
+                        }
+                    };
+
+                    if x > 0 {
+                        visit(x - 1, y);
+                    }
+                    if x + 1 < len_x {
+                        visit(x + 1, y);
+                    }
+                    if y > 0 {
+                        visit(x, y - 1);
+                    }
+                    if y + 1 < len_y {
+                        visit(x, y + 1);
+                    }
+                }
+
+                if body.len() >= min_area {
+                    continue;
+                }
+
+                for &(x, y) in &body {
+                    let below = match z {
+                        0 => Block::Air,
+                        _ => map.0[[x, y, z - 1]],
+                    };
+
+                    map.0[[x, y, z]] = if below.is_solid() { below } else { Block::Air };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 7×7 map with a floor of rock, and a basin in the middle enclosed by
+    /// a rim 3 blocks high
+    fn basin() -> IsoMap {
+        let mut iso_map = IsoMap::new_empty_dims(7, 7, 5);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0.slice_mut(s![1..6, 1..6, 1..3]).fill(Block::Rock);
+        iso_map.0.slice_mut(s![2..5, 2..5, 1..3]).fill(Block::Air);
+        iso_map
+    }
+
+    #[test]
+    fn enclosed_basin_stays_dry() {
+        let mut iso_map = basin();
+        flood_fill_water(&mut iso_map, 3);
+
+        assert_eq!(iso_map.0[[3, 3, 1]], Block::Air);
+        assert_eq!(iso_map.0[[0, 0, 1]], Block::Water);
+        assert_eq!(iso_map.0[[6, 3, 2]], Block::Water);
+        assert_eq!(iso_map.0[[0, 0, 3]], Block::Air);
+        assert_eq!(iso_map.0[[0, 0, 0]], Block::Rock);
+    }
+
+    #[test]
+    fn water_flows_into_opening() {
+        let mut iso_map = basin();
+        // Break the rim down to the floor of the basin
+        iso_map.0.slice_mut(s![1, 3, 1..3]).fill(Block::Air);
+        flood_fill_water(&mut iso_map, 2);
+
+        assert_eq!(iso_map.0[[3, 3, 1]], Block::Water);
+        assert_eq!(iso_map.0[[3, 3, 2]], Block::Air);
+    }
+
+    #[test]
+    fn small_bodies_removed() {
+        let mut iso_map = basin();
+        flood_fill_water(&mut iso_map, 3);
+
+        // A puddle of two blocks, two deep, in the dry basin, with a single
+        // block of water under the rock in one corner of it
+        iso_map.0.slice_mut(s![2..4, 2, 1..3]).fill(Block::Water);
+        iso_map.0[[2, 2, 0]] = Block::Water;
+
+        // The water outside of the rim is 24 blocks around
+        let mut lakes = iso_map.clone();
+        remove_small_water(&mut lakes, 25, 0..5);
+        assert_eq!(lakes.block_counts()[&Block::Water], 0);
+
+        remove_small_water(&mut iso_map, 3, 1..5);
+        assert_eq!(iso_map.0[[3, 2, 1]], Block::Rock);
+        assert_eq!(iso_map.0[[3, 2, 2]], Block::Rock);
+        assert_eq!(iso_map.0[[2, 2, 1]], Block::Air);
+        assert_eq!(iso_map.0[[2, 2, 2]], Block::Air);
+        assert_eq!(iso_map.0[[2, 2, 0]], Block::Water);
+        assert_eq!(iso_map.0[[0, 0, 1]], Block::Water);
+    }
+
+    #[test]
+    fn level_above_map() {
+        let mut iso_map = IsoMap::new_empty_dims(2, 3, 4);
+        flood_fill_water(&mut iso_map, 10);
+
+        assert!(iso_map.0.iter().all(|&block| block == Block::Water));
+    }
+}
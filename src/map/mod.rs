@@ -1,17 +1,124 @@
 //! Things related to representing a map
 
+pub mod ascii;
+pub mod encoding;
+pub mod export;
 pub mod generator;
 
-use ndarray::{Array3, Axis};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::iter;
+use std::ops::Range;
+use std::slice;
+use std::str::FromStr;
+
+use enum_iterator::IntoEnumIterator;
+use failure::Fail;
+use ndarray::{Array2, Array3, ArrayView2, Axis};
+use sdl2::pixels::Color;
+
+pub use map::encoding::{MapDecodeError, MapDecodeErrorKind};
 
 /// A single block of a certain type
-#[derive(Copy, IntoEnumIterator, Clone, PartialEq, Eq, Debug, Deserialize, Hash)]
+///
+/// Besides the named blocks, which generators produce and every renderer has
+/// tiles for, there are custom blocks, which are identified only by a number.
+/// These are for materials cubeglobe knows nothing about, so that maps built
+/// by hand can be rendered with tilesets of their own.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize, Hash)]
 pub enum Block {
     Air,
     Rock,
     Grass,
     Soil,
     Water,
+    Sand,
+    Snow,
+
+    /// A block made of the material with the given id
+    ///
+    /// Custom blocks are solid and opaque. In a renderer config, their tiles
+    /// are given with `kind = { Custom = 3 }`, and unlike named blocks, they
+    /// only need tiles if they're in the map being rendered.
+    Custom(u8),
+}
+
+/// Every named block, that is, every block other than custom ones
+static NAMED_BLOCKS: [Block; 7] = [
+    Block::Air,
+    Block::Rock,
+    Block::Grass,
+    Block::Soil,
+    Block::Water,
+    Block::Sand,
+    Block::Snow,
+];
+
+/// Iterating over blocks goes over the named blocks only, since there's a
+/// custom block for every id whether or not anything uses it
+impl IntoEnumIterator for Block {
+    type Iterator = iter::Cloned<slice::Iter<'static, Block>>;
+
+    fn into_enum_iter() -> Self::Iterator {
+        NAMED_BLOCKS.iter().cloned()
+    }
+}
+
+impl Block {
+    /// Whether the block is solid ground, which can be stood on and which
+    /// water can't flow through
+    ///
+    /// Air and water are not solid, every other block is.
+    pub fn is_solid(&self) -> bool {
+        match *self {
+            Block::Air | Block::Water => false,
+            Block::Rock
+            | Block::Grass
+            | Block::Soil
+            | Block::Sand
+            | Block::Snow
+            | Block::Custom(_) => true,
+        }
+    }
+
+    /// Whether the block can't be seen through, hiding anything behind it
+    ///
+    /// Air and water are not opaque, every other block is.
+    pub fn is_opaque(&self) -> bool {
+        match *self {
+            Block::Air | Block::Water => false,
+            Block::Rock
+            | Block::Grass
+            | Block::Soil
+            | Block::Sand
+            | Block::Snow
+            | Block::Custom(_) => true,
+        }
+    }
+
+    /// A color which stands for the block, for when it's drawn without tiles
+    ///
+    /// Air is fully transparent. Custom blocks get a color picked from their
+    /// id, so different ids can be told apart, but nothing more is promised
+    /// about it. Anything which draws blocks this way should still let its
+    /// colors be overridden.
+    pub fn default_color(&self) -> Color {
+        match *self {
+            Block::Air => Color::RGBA(154, 216, 224, 0),
+            Block::Rock => Color::RGB(128, 128, 128),
+            Block::Grass => Color::RGB(86, 160, 48),
+            Block::Soil => Color::RGB(120, 80, 40),
+            Block::Water => Color::RGB(40, 90, 200),
+            Block::Sand => Color::RGB(220, 200, 140),
+            Block::Snow => Color::RGB(240, 245, 255),
+            Block::Custom(id) => Color::RGB(
+                id.wrapping_mul(97) ^ 0x55,
+                id.wrapping_mul(59).wrapping_add(128),
+                id.wrapping_mul(31) ^ 0xaa,
+            ),
+        }
+    }
 }
 
 impl Default for Block {
@@ -20,15 +127,64 @@ impl Default for Block {
     }
 }
 
+/// Blocks are shown by the same names as in renderer configs, such as `Rock`,
+/// with custom blocks shown as `Custom(3)`
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Block::Custom(id) => write!(f, "Custom({})", id),
+            block => write!(f, "{:?}", block),
+        }
+    }
+}
+
+/// Blocks are parsed from the names they're shown by, ignoring case
+impl FromStr for Block {
+    type Err = ParseBlockError;
+
+    fn from_str(s: &str) -> Result<Block, ParseBlockError> {
+        let name = s.trim().to_lowercase();
+
+        if let Some(named) = NAMED_BLOCKS
+            .iter()
+            .find(|block| block.to_string().to_lowercase() == name)
+        {
+            return Ok(*named);
+        }
+
+        if name.starts_with("custom(") && name.ends_with(')') {
+            if let Ok(id) = name["custom(".len()..name.len() - 1].trim().parse() {
+                return Ok(Block::Custom(id));
+            }
+        }
+
+        Err(ParseBlockError(s.to_string()))
+    }
+}
+
+/// An attempt to parse a string which isn't the name of any block
+#[derive(Fail, Debug, Clone, PartialEq, Eq)]
+#[fail(display = "There's no block called {:?}", _0)]
+pub struct ParseBlockError(pub String);
+
 /// Struct representing a three dimensional map of blocks
 ///
 /// Order is (x,y,z), z+ is up. Although it's called `IsoMap`, there is nothing
 /// inherently isometric about it, other than the fact it's intended to be
 /// rendered in an isometric perspective.
-#[derive(Clone)]
+///
+/// The underlying array is public, but [`get`](#method.get) and
+/// [`set`](#method.set) are the preferred way to access blocks, since they
+/// don't panic on positions outside of the map. Likewise,
+/// [`shape`](#method.shape) and [`floors`](#method.floors) should be used
+/// instead of going through the array, since the way the blocks are stored may
+/// change.
+///
+/// Maps are equal if they have the same dimensions and the same blocks. To
+/// find out where two maps differ, use [`diff`](#method.diff).
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IsoMap(pub Array3<Block>);
 
-#[cfg_attr(feature = "cargo-clippy", allow(len_without_is_empty))]
 impl IsoMap {
     /// Create a new cube-shaped IsoMap, with `len` tiles in every direction,
     /// filled with [`Block::Air`](enum.Block.html#variant.Air).
@@ -36,13 +192,267 @@ impl IsoMap {
         IsoMap(Array3::default((len, len, len)))
     }
 
+    /// Create a new IsoMap with `x`, `y` and `z` tiles along the respective
+    /// axes, filled with [`Block::Air`](enum.Block.html#variant.Air).
+    pub fn new_empty_dims(x: usize, y: usize, z: usize) -> IsoMap {
+        IsoMap(Array3::default((x, y, z)))
+    }
+
     /// Get the length of the map
     ///
-    /// The map is a cube, every edge is the same length. This function returns
-    /// the edge length.
+    /// For a cube-shaped map, every edge is the same length, and this function
+    /// returns the edge length. For other maps, this is the length along the x
+    /// axis, and [`dim_x`](#method.dim_x), [`dim_y`](#method.dim_y) and
+    /// [`dim_z`](#method.dim_z) should be used instead.
     pub fn len(&self) -> usize {
         self.0.len_of(Axis(0))
     }
+
+    /// Copy the blocks within the given ranges into a new, smaller map
+    ///
+    /// Ranges are clamped to the bounds of the map, so asking for more than
+    /// there is returns only what's there, and a range entirely outside the
+    /// map results in a map with a length of 0 along that axis.
+    pub fn slice_region(&self, x: Range<usize>, y: Range<usize>, z: Range<usize>) -> IsoMap {
+        let clamp = |range: Range<usize>, len: usize| {
+            let end = range.end.min(len);
+            range.start.min(end)..end
+        };
+
+        let x = clamp(x, self.dim_x());
+        let y = clamp(y, self.dim_y());
+        let z = clamp(z, self.dim_z());
+
+        let dims = (x.end - x.start, y.end - y.start, z.end - z.start);
+
+        IsoMap(Array3::from_shape_fn(dims, |(i, j, k)| {
+            self.0[[x.start + i, y.start + j, z.start + k]]
+        }))
+    }
+
+    /// Copy the smallest part of the map which holds every block that isn't
+    /// [`Block::Air`](enum.Block.html#variant.Air) into a new map
+    ///
+    /// Generated maps are often mostly air above the terrain, which still
+    /// takes up room when rendered, so trimming it makes for smaller output.
+    /// Air below and beside the blocks is trimmed as well. A map with nothing
+    /// in it is trimmed down to a single block of air.
+    pub fn trim_air(&self) -> IsoMap {
+        self.trim_air_padded(0)
+    }
+
+    /// Like [`trim_air`](#method.trim_air), but keeping up to `padding`
+    /// blocks of the map around the ones that aren't air on every side
+    ///
+    /// Padding never goes past the edges of the map.
+    pub fn trim_air_padded(&self, padding: usize) -> IsoMap {
+        let bounds = self
+            .0
+            .indexed_iter()
+            .filter(|&(_, block)| block != &Block::Air)
+            .fold(None, |bounds, ((x, y, z), _)| match bounds {
+                None => Some(([x, y, z], [x, y, z])),
+                Some((low, high)) => {
+                    let low = [low[0].min(x), low[1].min(y), low[2].min(z)];
+                    let high = [high[0].max(x), high[1].max(y), high[2].max(z)];
+                    Some((low, high))
+                }
+            });
+
+        let (low, high) = match bounds {
+            Some(bounds) => bounds,
+            None => return IsoMap::new_empty(1),
+        };
+
+        // slice_region takes care of padding past the far edges
+        let range = |axis: usize| low[axis].saturating_sub(padding)..high[axis] + 1 + padding;
+
+        self.slice_region(range(0), range(1), range(2))
+    }
+
+    /// Copy every block of `other` that isn't air into this map, with block
+    /// 0,0,0 of `other` placed at `offset`
+    ///
+    /// Air in `other` is transparent, leaving this map untouched. Anything that
+    /// would end up outside this map is clipped.
+    pub fn overlay(&mut self, other: &IsoMap, offset: (usize, usize, usize)) {
+        let (off_x, off_y, off_z) = offset;
+
+        for ((x, y, z), block) in other.0.indexed_iter() {
+            if block == &Block::Air {
+                continue;
+            }
+
+            if let Some(target) = self.0.get_mut([x + off_x, y + off_y, z + off_z]) {
+                *target = *block;
+            }
+        }
+    }
+
+    /// Replace every block in the map with the result of calling `f` on it
+    ///
+    /// This is handy for restyling a finished map, such as by turning all of
+    /// its grass into snow.
+    pub fn map_blocks(&mut self, f: impl Fn(Block) -> Block) {
+        self.0.mapv_inplace(f);
+    }
+
+    /// Replace every block of type `from` in the map with `to`
+    pub fn replace(&mut self, from: Block, to: Block) {
+        self.map_blocks(|block| if block == from { to } else { block });
+    }
+
+    /// Check whether the map has nothing in it
+    ///
+    /// Unlike collections, where `is_empty` means the length is 0, a map is
+    /// empty when every block in it is [`Block::Air`](enum.Block.html#variant.Air),
+    /// regardless of its dimensions. A map with no blocks at all is also
+    /// empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|b| b == &Block::Air)
+    }
+
+    /// Count the blocks which aren't [`Block::Air`](enum.Block.html#variant.Air)
+    pub fn count_non_air(&self) -> usize {
+        self.0.iter().filter(|b| b != &&Block::Air).count()
+    }
+
+    /// Iterate over the surface of the map, that is, the topmost block that
+    /// isn't [`Block::Air`](enum.Block.html#variant.Air) in every column
+    ///
+    /// Items are `(x, y, z, block)`, with columns in order of x, and then y.
+    /// Columns which are entirely air are skipped.
+    pub fn surface_iter(&self) -> impl Iterator<Item = (usize, usize, usize, Block)> + '_ {
+        let (len_x, len_y, _) = self.shape();
+
+        (0..len_x)
+            .flat_map(move |x| (0..len_y).map(move |y| (x, y)))
+            .filter_map(move |(x, y)| {
+                let column = self.0.slice(s![x, y, ..]);
+
+                column
+                    .iter()
+                    .rposition(|&block| block != Block::Air)
+                    .map(|z| (x, y, z, column[z]))
+            })
+    }
+
+    /// Get the z index of the topmost block that isn't
+    /// [`Block::Air`](enum.Block.html#variant.Air) in every column
+    ///
+    /// The result is indexed by x and y. Columns which are entirely air are
+    /// 0, the same as columns with only a single block at the bottom, so use
+    /// [`surface_iter`](#method.surface_iter) where the two need to be told
+    /// apart.
+    pub fn heightmap(&self) -> Array2<usize> {
+        let (len_x, len_y, _) = self.shape();
+        let mut heights = Array2::zeros((len_x, len_y));
+
+        for (x, y, z, _) in self.surface_iter() {
+            heights[[x, y]] = z;
+        }
+
+        heights
+    }
+
+    /// Count the blocks of every type in the map
+    ///
+    /// Every type of block has an entry, even if there are none of it.
+    pub fn block_counts(&self) -> HashMap<Block, usize> {
+        let mut counts: HashMap<Block, usize> = Block::into_enum_iter().map(|b| (b, 0)).collect();
+
+        for block in self.0.iter() {
+            *counts.entry(*block).or_default() += 1;
+        }
+
+        counts
+    }
+
+    /// Get the lengths of the map along the x, y and z axes
+    pub fn shape(&self) -> (usize, usize, usize) {
+        self.0.dim()
+    }
+
+    /// Iterate over the floors of the map, from the bottom up
+    ///
+    /// Every floor is a view of the blocks at one z index, indexed by x and
+    /// y.
+    pub fn floors(&self) -> impl Iterator<Item = ArrayView2<'_, Block>> + '_ {
+        self.0.axis_iter(Axis(2))
+    }
+
+    /// Get the length of the map along the x axis
+    pub fn dim_x(&self) -> usize {
+        self.0.len_of(Axis(0))
+    }
+
+    /// Get the length of the map along the y axis
+    pub fn dim_y(&self) -> usize {
+        self.0.len_of(Axis(1))
+    }
+
+    /// Get the height of the map, that is, its length along the z axis
+    pub fn dim_z(&self) -> usize {
+        self.0.len_of(Axis(2))
+    }
+
+    /// Get the block at `x`, `y`, `z`
+    ///
+    /// Returns `None` if the position is outside of the map.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> Option<Block> {
+        self.0.get([x, y, z]).cloned()
+    }
+
+    /// Set the block at `x`, `y`, `z` to `block`
+    ///
+    /// Returns an error if the position is outside of the map, in which case
+    /// the map is left unchanged.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: Block) -> Result<(), OutOfBounds> {
+        match self.0.get_mut([x, y, z]) {
+            Some(b) => {
+                *b = block;
+                Ok(())
+            }
+            None => Err(OutOfBounds { x, y, z }),
+        }
+    }
+
+    /// List the positions where this map and `other` have different blocks
+    ///
+    /// Items are `(x, y, z, ours, theirs)`, where `ours` is the block in this
+    /// map and `theirs` the one in `other`, in order of x, then y, then z. If
+    /// the maps are different sizes, positions outside of one of them are
+    /// treated as air there, so the list is empty only when the maps are equal
+    /// apart from air beyond the edges of the smaller one.
+    pub fn diff(&self, other: &IsoMap) -> Vec<(usize, usize, usize, Block, Block)> {
+        let (ours_x, ours_y, ours_z) = self.shape();
+        let (theirs_x, theirs_y, theirs_z) = other.shape();
+        let mut differences = Vec::new();
+
+        for x in 0..ours_x.max(theirs_x) {
+            for y in 0..ours_y.max(theirs_y) {
+                for z in 0..ours_z.max(theirs_z) {
+                    let ours = self.get(x, y, z).unwrap_or(Block::Air);
+                    let theirs = other.get(x, y, z).unwrap_or(Block::Air);
+
+                    if ours != theirs {
+                        differences.push((x, y, z, ours, theirs));
+                    }
+                }
+            }
+        }
+
+        differences
+    }
+}
+
+/// An attempt to access a position outside of an `IsoMap`
+#[derive(Fail, Debug, Clone, Copy, PartialEq, Eq)]
+#[fail(display = "Position ({}, {}, {}) is outside of the map", x, y, z)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
+    pub z: usize,
 }
 
 #[cfg(test)]
@@ -63,10 +473,273 @@ mod tests {
         )
     }
 
+    #[test]
+    fn shape_and_floors() {
+        let mut iso_map = IsoMap::new_empty_dims(2, 3, 4);
+        iso_map.0[[1, 2, 0]] = Block::Rock;
+        iso_map.0[[0, 1, 3]] = Block::Snow;
+
+        assert_eq!(iso_map.shape(), (2, 3, 4));
+
+        let floors: Vec<ArrayView2<Block>> = iso_map.floors().collect();
+        assert_eq!(floors.len(), 4);
+        assert_eq!(floors[0].dim(), (2, 3));
+        assert_eq!(floors[0][[1, 2]], Block::Rock);
+        assert_eq!(floors[3][[0, 1]], Block::Snow);
+        assert!(floors[1].iter().all(|&block| block == Block::Air));
+    }
+
+    #[test]
+    fn equality_and_diff() {
+        let mut ours = IsoMap::new_empty(3);
+        let mut theirs = IsoMap::new_empty(3);
+        ours.0[[0, 0, 0]] = Block::Rock;
+        theirs.0[[0, 0, 0]] = Block::Rock;
+        assert_eq!(ours, theirs);
+        assert!(ours.diff(&theirs).is_empty());
+
+        theirs.0[[1, 2, 0]] = Block::Water;
+        theirs.0[[0, 0, 0]] = Block::Sand;
+        assert_ne!(ours, theirs);
+        assert_eq!(
+            ours.diff(&theirs),
+            vec![
+                (0, 0, 0, Block::Rock, Block::Sand),
+                (1, 2, 0, Block::Air, Block::Water),
+            ]
+        );
+
+        // Beyond the edges of the smaller map counts as air
+        let mut taller = IsoMap::new_empty_dims(3, 3, 4);
+        taller.0.slice_mut(s![.., .., ..3]).assign(&ours.0);
+        assert_ne!(ours, taller);
+        assert!(ours.diff(&taller).is_empty());
+
+        taller.0[[2, 2, 3]] = Block::Snow;
+        assert_eq!(taller.diff(&ours), vec![(2, 2, 3, Block::Snow, Block::Air)]);
+    }
+
+    #[test]
+    fn classify_blocks() {
+        let solid: Vec<Block> = Block::into_enum_iter().filter(Block::is_solid).collect();
+        let opaque: Vec<Block> = Block::into_enum_iter().filter(Block::is_opaque).collect();
+        let expected = vec![
+            Block::Rock,
+            Block::Grass,
+            Block::Soil,
+            Block::Sand,
+            Block::Snow,
+        ];
+
+        assert_eq!(solid, expected);
+        assert_eq!(opaque, expected);
+        assert!(Block::Custom(5).is_solid() && Block::Custom(5).is_opaque());
+    }
+
+    #[test]
+    fn block_names() {
+        for block in Block::into_enum_iter().chain(vec![Block::Custom(0), Block::Custom(255)]) {
+            assert_eq!(block.to_string().parse::<Block>(), Ok(block));
+        }
+
+        assert_eq!(Block::Water.to_string(), "Water");
+        assert_eq!(Block::Custom(12).to_string(), "Custom(12)");
+        assert_eq!("sNoW".parse::<Block>(), Ok(Block::Snow));
+        assert_eq!(" custom( 7 ) ".parse::<Block>(), Ok(Block::Custom(7)));
+
+        for name in &["Lava", "Custom(256)", "Custom()", ""] {
+            assert_eq!(name.parse::<Block>(), Err(ParseBlockError(name.to_string())));
+        }
+    }
+
+    #[test]
+    fn default_colors() {
+        assert_eq!(Block::Air.default_color().a, 0);
+
+        let colors: Vec<Color> = Block::into_enum_iter()
+            .chain((0..8).map(Block::Custom))
+            .map(|block| block.default_color())
+            .collect();
+        for (i, a) in colors.iter().enumerate() {
+            for b in &colors[i + 1..] {
+                assert!(a != b);
+            }
+        }
+    }
+
+    #[test]
+    fn surface() {
+        let mut iso_map = IsoMap::new_empty_dims(2, 2, 4);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[0, 0, 1]] = Block::Grass;
+        iso_map.0[[0, 1, 3]] = Block::Snow;
+        // Only the topmost block counts, even with air under it
+        iso_map.0[[1, 1, 0]] = Block::Water;
+        iso_map.0[[1, 1, 2]] = Block::Rock;
+
+        let surface: Vec<(usize, usize, usize, Block)> = iso_map.surface_iter().collect();
+
+        assert_eq!(
+            surface,
+            vec![
+                (0, 0, 1, Block::Grass),
+                (0, 1, 3, Block::Snow),
+                (1, 1, 2, Block::Rock),
+            ]
+        );
+        assert_eq!(IsoMap::new_empty(3).surface_iter().count(), 0);
+    }
+
+    #[test]
+    fn heightmap() {
+        let mut iso_map = IsoMap::new_empty_dims(3, 2, 4);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[1, 0, 2]] = Block::Grass;
+        iso_map.0[[2, 1, 3]] = Block::Snow;
+        iso_map.0[[2, 1, 1]] = Block::Rock;
+
+        let heights = iso_map.heightmap();
+
+        assert_eq!(heights.dim(), (3, 2));
+        assert_eq!(heights, array![[0, 0], [2, 0], [0, 3]]);
+    }
+
     #[test]
     fn return_len() {
         let iso_map = IsoMap::new_empty(50);
         
         assert_eq!(iso_map.len(), 50)
     }
+
+    #[test]
+    fn empty_map() {
+        let mut iso_map = IsoMap::new_empty(4);
+
+        assert!(iso_map.is_empty());
+        assert_eq!(iso_map.count_non_air(), 0);
+
+        iso_map.0[[1, 2, 3]] = Block::Rock;
+        iso_map.0[[0, 0, 0]] = Block::Water;
+
+        assert!(!iso_map.is_empty());
+        assert_eq!(iso_map.count_non_air(), 2);
+    }
+
+    #[test]
+    fn count_blocks() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[1, 1, 1]] = Block::Water;
+
+        let counts = iso_map.block_counts();
+
+        assert_eq!(counts[&Block::Rock], 9);
+        assert_eq!(counts[&Block::Water], 1);
+        assert_eq!(counts[&Block::Air], 17);
+        assert_eq!(counts[&Block::Sand], 0);
+    }
+
+    #[test]
+    fn region() {
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0[[1, 2, 3]] = Block::Rock;
+
+        let region = iso_map.slice_region(1..3, 2..10, 3..4);
+
+        assert_eq!(region.0.shape(), &[2, 2, 1]);
+        assert_eq!(region.get(0, 0, 0), Some(Block::Rock));
+        assert_eq!(region.count_non_air(), 1);
+
+        let outside = iso_map.slice_region(5..8, 0..4, 0..4);
+        assert_eq!(outside.0.shape(), &[0, 4, 4]);
+    }
+
+    #[test]
+    fn trim_to_blocks() {
+        let mut iso_map = IsoMap::new_empty(8);
+        iso_map.0.slice_mut(s![2..5, 3..4, 0..2]).fill(Block::Rock);
+        iso_map.0[[4, 6, 2]] = Block::Grass;
+
+        let trimmed = iso_map.trim_air();
+        assert_eq!(trimmed.shape(), (3, 4, 3));
+        assert_eq!(trimmed.count_non_air(), iso_map.count_non_air());
+        assert_eq!(trimmed.get(2, 3, 2), Some(Block::Grass));
+
+        // Padding stops at the edges of the map
+        let padded = iso_map.trim_air_padded(2);
+        assert_eq!(padded.shape(), (7, 7, 5));
+        assert_eq!(padded.get(2, 2, 0), Some(Block::Rock));
+        assert_eq!(padded.get(4, 5, 2), Some(Block::Grass));
+
+        assert_eq!(IsoMap::new_empty(5).trim_air(), IsoMap::new_empty(1));
+        assert_eq!(IsoMap::new_empty(0).trim_air_padded(3), IsoMap::new_empty(1));
+    }
+
+    #[test]
+    fn overlay_map() {
+        let mut base = IsoMap::new_empty(4);
+        base.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+
+        let mut stamp = IsoMap::new_empty(2);
+        stamp.0[[0, 0, 0]] = Block::Soil;
+        stamp.0[[1, 1, 1]] = Block::Grass;
+
+        base.overlay(&stamp, (3, 3, 0));
+
+        // Air in the stamp doesn't overwrite anything...
+        assert_eq!(base.get(3, 3, 0), Some(Block::Soil));
+        assert_eq!(base.get(2, 3, 0), Some(Block::Rock));
+
+        // ...and what doesn't fit is clipped
+        assert_eq!(base.count_non_air(), 16);
+    }
+
+    #[test]
+    fn substitute_blocks() {
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Soil);
+        iso_map.0[[0, 0, 1]] = Block::Grass;
+
+        iso_map.replace(Block::Soil, Block::Sand);
+        assert_eq!(iso_map.block_counts()[&Block::Sand], 4);
+        assert_eq!(iso_map.block_counts()[&Block::Soil], 0);
+
+        iso_map.map_blocks(|block| match block {
+            Block::Air => Block::Air,
+            _ => Block::Rock,
+        });
+        assert_eq!(iso_map.block_counts()[&Block::Rock], 5);
+        assert_eq!(iso_map.get(1, 1, 1), Some(Block::Air));
+    }
+
+    #[test]
+    fn rectangular_dims() {
+        let iso_map = IsoMap::new_empty_dims(4, 5, 2);
+
+        assert_eq!(iso_map.0.shape(), &[4, 5, 2]);
+        assert_eq!(
+            (iso_map.dim_x(), iso_map.dim_y(), iso_map.dim_z()),
+            (4, 5, 2)
+        );
+    }
+
+    #[test]
+    fn get_and_set() {
+        let mut iso_map = IsoMap::new_empty(3);
+
+        assert_eq!(iso_map.set(1, 2, 0, Block::Rock), Ok(()));
+        assert_eq!(iso_map.get(1, 2, 0), Some(Block::Rock));
+        assert_eq!(iso_map.get(0, 0, 0), Some(Block::Air));
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut iso_map = IsoMap::new_empty(3);
+
+        assert_eq!(iso_map.get(3, 0, 0), None);
+        assert_eq!(
+            iso_map.set(0, 0, 3, Block::Rock),
+            Err(OutOfBounds { x: 0, y: 0, z: 3 })
+        );
+    }
 }
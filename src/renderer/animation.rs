@@ -0,0 +1,255 @@
+//! Encoding a series of renders as an animated GIF
+
+use std::fs::File as FsFile;
+
+use color_quant::NeuQuant;
+use gif::{DisposalMethod, Encoder, Frame, Repeat, SetParameter};
+
+use super::*;
+
+/// How many pixels the quantizer skips when building the palette, between 1
+/// for the best quality and 30 for the fastest
+const QUANTIZER_SAMPLING: i32 = 10;
+
+/// Write `frames` to `path` as an animated GIF which loops forever
+///
+/// Every frame is shown for `delay_cs` hundredths of a second. GIFs can only
+/// have 256 colors, so a palette is picked for all of the frames together,
+/// which keeps colors from flickering between frames. Frames of different
+/// sizes are padded to the size of the largest one: they're lined up along
+/// their bottom edge and centred horizontally, with the padding filled with
+/// the color of their upper left pixel, which for renders is the background.
+/// Fully transparent pixels stay transparent.
+///
+/// ## Example use
+/// ```no_run
+/// use cubeglobe::map::generator::TerGenOne;
+/// use cubeglobe::renderer::{encode_gif, Renderer};
+/// use std::path::Path;
+///
+/// let renderer = Renderer::from_config_file(Path::new("tiles.toml")).unwrap();
+/// let slices = TerGenOne::new().set_len(32).generate_slices();
+/// let frames = renderer.render_frames(&slices).unwrap();
+///
+/// encode_gif(&frames, Path::new("slices.gif"), 10).unwrap();
+/// ```
+pub fn encode_gif(frames: &[Surface], path: &Path, delay_cs: u16) -> Result<(), RendererError> {
+    let width = frames.iter().map(|frame| frame.width()).max();
+    let height = frames.iter().map(|frame| frame.height()).max();
+    let (width, height) = match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err(RendererError::NoFrames),
+    };
+
+    if width > u32::from(u16::MAX) || height > u32::from(u16::MAX) {
+        return Err(RendererError::OutputTooLarge {
+            width: u64::from(width),
+            height: u64::from(height),
+        });
+    }
+
+    let padded = frames
+        .iter()
+        .map(|frame| padded_rgba(frame, width, height))
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+
+    // Transparent pixels get a palette entry of their own, so that no opaque
+    // color can end up sharing it
+    let opaque: Vec<u8> = padded
+        .iter()
+        .flat_map(|pixels| pixels.chunks(4))
+        .filter(|pixel| pixel[3] != 0)
+        .flatten()
+        .cloned()
+        .collect();
+    let any_transparent = padded
+        .iter()
+        .flat_map(|pixels| pixels.chunks(4))
+        .any(|pixel| pixel[3] == 0);
+
+    let colors = if any_transparent { 255 } else { 256 };
+    let quantizer = NeuQuant::new(QUANTIZER_SAMPLING, colors, &opaque);
+    let mut palette = quantizer.color_map_rgb();
+    let transparent = if any_transparent {
+        palette.extend_from_slice(&[0, 0, 0]);
+        Some(colors as u8)
+    } else {
+        None
+    };
+
+    let file = FsFile::create(path)?;
+    let mut encoder = Encoder::new(file, width as u16, height as u16, &palette)?;
+    encoder.set(Repeat::Infinite)?;
+
+    for pixels in padded {
+        let frame = Frame {
+            delay: delay_cs,
+            // Clearing each frame first keeps the previous one from showing
+            // through transparent pixels
+            dispose: DisposalMethod::Background,
+            transparent,
+            width: width as u16,
+            height: height as u16,
+            buffer: pixels
+                .chunks(4)
+                .map(|pixel| match transparent {
+                    Some(index) if pixel[3] == 0 => index,
+                    _ => quantizer.index_of(pixel) as u8,
+                })
+                .collect::<Vec<u8>>()
+                .into(),
+            ..Frame::default()
+        };
+
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
+
+/// Get the pixels of `frame` as RGBA8, padded to `width` by `height`
+///
+/// Partially transparent pixels are made opaque, and fully transparent ones
+/// are all made the same transparent black, since that's all a GIF can hold.
+fn padded_rgba(frame: &Surface, width: u32, height: u32) -> Result<Vec<u8>, String> {
+    // An empty surface has no pixels to lock, so an empty frame is all padding
+    if frame.width() == 0 || frame.height() == 0 {
+        return Ok(vec![0; width as usize * height as usize * 4]);
+    }
+
+    let converted = match frame.pixel_format_enum() {
+        PixelFormatEnum::RGB24 | PixelFormatEnum::RGBA32 => None,
+        _ => Some(frame.convert_format(PixelFormatEnum::RGBA32)?),
+    };
+    let frame = converted.as_ref().unwrap_or(frame);
+
+    let mut pixels = surface_to_rgba(frame);
+    for pixel in pixels.chunks_mut(4) {
+        if pixel[3] == 0 {
+            pixel.copy_from_slice(&[0, 0, 0, 0]);
+        } else {
+            pixel[3] = 255;
+        }
+    }
+
+    let (frame_width, frame_height) = (frame.width() as usize, frame.height() as usize);
+    let (width, height) = (width as usize, height as usize);
+    if (frame_width, frame_height) == (width, height) {
+        return Ok(pixels);
+    }
+
+    let fill = match pixels.get(..4) {
+        Some(pixel) => pixel.to_vec(),
+        None => vec![0, 0, 0, 0],
+    };
+    let mut out: Vec<u8> = fill.iter().cloned().cycle().take(width * height * 4).collect();
+
+    let left = (width - frame_width) / 2;
+    let top = height - frame_height;
+    for (y, row) in pixels.chunks(frame_width * 4).enumerate() {
+        let start = ((top + y) * width + left) * 4;
+        out[start..start + row.len()].copy_from_slice(row);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gif::Decoder;
+    use std::env;
+
+    fn solid(width: u32, height: u32, color: Color) -> Surface<'static> {
+        let mut surf = Surface::new(width, height, PixelFormatEnum::RGB24).unwrap();
+        surf.fill_rect(None, color).unwrap();
+        surf
+    }
+
+    #[test]
+    fn padding() {
+        let mut frame = solid(2, 1, Color::RGB(0, 0, 0));
+        frame.fill_rect(Rect::new(1, 0, 1, 1), Color::RGB(255, 0, 0)).unwrap();
+        let pixels = padded_rgba(&frame, 4, 2).unwrap();
+
+        // Bottom row, centred, with the padding in the upper left color
+        let black = [0, 0, 0, 255];
+        let red = [255, 0, 0, 255];
+        let expected: Vec<u8> = [black; 5]
+            .iter()
+            .chain(&[black, red, black])
+            .flatten()
+            .cloned()
+            .collect();
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn padding_empty_frame() {
+        let frame = Surface::new(0, 3, PixelFormatEnum::RGB24).unwrap();
+        let pixels = padded_rgba(&frame, 2, 3).unwrap();
+
+        assert_eq!(pixels, vec![0; 2 * 3 * 4]);
+    }
+
+    #[test]
+    fn frames_and_timing() {
+        let frames = vec![
+            solid(8, 6, Color::RGB(255, 0, 0)),
+            solid(10, 4, Color::RGB(0, 0, 255)),
+            solid(10, 6, Color::RGB(0, 255, 0)),
+        ];
+
+        let mut path = env::temp_dir();
+        path.push(format!("cubeglobe-anim-{}.gif", std::process::id()));
+        encode_gif(&frames, &path, 25).unwrap();
+
+        let mut reader = Decoder::new(FsFile::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        assert_eq!((reader.width(), reader.height()), (10, 6));
+        assert!(reader.global_palette().is_some());
+
+        let mut count = 0;
+        while let Some(frame) = reader.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 25);
+            assert_eq!((frame.width, frame.height), (10, 6));
+            assert!(frame.palette.is_none());
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn transparent_entry_is_unique() {
+        let mut frame = Surface::new(4, 4, PixelFormatEnum::RGBA32).unwrap();
+        frame.fill_rect(None, Color::RGBA(0, 0, 0, 0)).unwrap();
+        frame.fill_rect(Rect::new(0, 0, 2, 4), Color::RGB(2, 2, 2)).unwrap();
+
+        let mut path = env::temp_dir();
+        path.push(format!("cubeglobe-transparent-{}.gif", std::process::id()));
+        encode_gif(&[frame], &path, 10).unwrap();
+
+        let mut reader = Decoder::new(FsFile::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let frame = reader.read_next_frame().unwrap().unwrap();
+        let transparent = frame.transparent.unwrap();
+        for (i, &index) in frame.buffer.iter().enumerate() {
+            // The left half is the near black opaque color
+            assert_eq!(index == transparent, i % 4 >= 2);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn no_frames() {
+        match encode_gif(&[], Path::new("never-written.gif"), 10) {
+            Err(RendererError::NoFrames) => {}
+            _ => panic!("expected NoFrames"),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Lines showing the directions of a map's axes, drawn over a render
+
+use super::legend::{text_pixels, FONT_PIXEL, GLYPH_HEIGHT};
+use super::*;
+
+/// Length of the lines, in blocks, unless the map is shorter than that
+const AXIS_LENGTH: usize = 2;
+
+/// Thickness of the lines, in output pixels, before scaling
+const LINE_WIDTH: u32 = 2;
+
+impl<'a> Renderer<'a> {
+    /// Draw lines along the x, y and z axes of `isomap` onto `target`, which
+    /// the map has been rendered to, starting from the bottom corner of block
+    /// 0,0,0
+    pub(super) fn draw_axes(
+        &self,
+        isomap: &IsoMap,
+        target: &mut Surface,
+    ) -> Result<(), RendererError> {
+        let (len_x, len_y, _) = isomap.shape();
+        let (view_len_x, view_len_y) = match self.facing {
+            Facing::North | Facing::South => (len_x, len_y),
+            Facing::East | Facing::West => (len_y, len_x),
+        };
+
+        let (_, surf_height) = self.checked_dimensions(isomap)?;
+        let origin = self.map_origin(isomap, view_len_y, surf_height);
+        let scale = self.scale as i32;
+
+        // Map positions are at block corners, with z at the bottom of a floor.
+        // The back corner of a block is at the top of its tile, in the middle,
+        // and the bottom of the block is the height of the sides below that.
+        let project = |x: usize, y: usize, z: usize| {
+            let (vx, vy) = view_corner(self.facing, view_len_x, view_len_y, x, y);
+            let floor_origin = origin.offset(0, -(self.sides_height() as i32 * z as i32));
+            let corner = self
+                .get_tile_pos(floor_origin, vx, vy)
+                .offset(self.width as i32 / 2, self.sides_height() as i32);
+
+            ((corner.x() * scale) as f32, (corner.y() * scale) as f32)
+        };
+
+        let start = project(0, 0, 0);
+        let axes = [
+            ("X", project(AXIS_LENGTH.min(len_x), 0, 0), Color::RGB(230, 40, 40)),
+            ("Y", project(0, AXIS_LENGTH.min(len_y), 0), Color::RGB(40, 200, 40)),
+            ("Z", project(0, 0, AXIS_LENGTH), Color::RGB(40, 80, 230)),
+        ];
+
+        let line_width = LINE_WIDTH * self.scale;
+        let font_pixel = FONT_PIXEL * self.scale;
+
+        for &(label, end, color) in &axes {
+            target.fill_rects(&line_rects(start, end, line_width), color)?;
+
+            // The label goes just past the end of the line, centred on it
+            let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+            let length = (dx * dx + dy * dy).sqrt().max(1.0);
+            let gap = (GLYPH_HEIGHT * font_pixel) as f32;
+            let label_x = end.0 + dx / length * gap - gap / 2.0;
+            let label_y = end.1 + dy / length * gap - gap / 2.0;
+
+            let rects = text_pixels(label)
+                .map(|(x, y)| {
+                    Rect::new(
+                        label_x as i32 + (x * font_pixel) as i32,
+                        label_y as i32 + (y * font_pixel) as i32,
+                        font_pixel,
+                        font_pixel,
+                    )
+                })
+                .collect::<Vec<Rect>>();
+            target.fill_rects(&rects, color)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the position of the corner `x`, `y` of the map in a map turned to
+/// `facing`, which is `len_x` by `len_y` blocks after turning
+///
+/// Unlike block positions, corners go from 0 up to the length along an axis,
+/// inclusive.
+fn view_corner(facing: Facing, len_x: usize, len_y: usize, x: usize, y: usize) -> (usize, usize) {
+    match facing {
+        Facing::North => (x, y),
+        Facing::East => (len_x - y, x),
+        Facing::South => (len_x - x, len_y - y),
+        Facing::West => (y, len_y - x),
+    }
+}
+
+/// Get squares `width` pixels wide which cover the line from `start` to `end`
+fn line_rects(start: (f32, f32), end: (f32, f32), width: u32) -> Vec<Rect> {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0) as u32;
+    let offset = width as f32 / 2.0;
+
+    (0..=steps)
+        .map(|step| {
+            let t = step as f32 / steps as f32;
+            Rect::new(
+                (start.0 + dx * t - offset) as i32,
+                (start.1 + dy * t - offset) as i32,
+                width,
+                width,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::test_config_path;
+
+    #[test]
+    fn corners_follow_facing() {
+        // The corner at the origin of a 3 by 2 map ends up at each corner of
+        // the view in turn, and the opposite corner along with it
+        let corners: Vec<((usize, usize), (usize, usize))> = Facing::into_enum_iter()
+            .map(|facing| {
+                let (len_x, len_y) = match facing {
+                    Facing::North | Facing::South => (3, 2),
+                    Facing::East | Facing::West => (2, 3),
+                };
+                (
+                    view_corner(facing, len_x, len_y, 0, 0),
+                    view_corner(facing, len_x, len_y, 3, 2),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            corners,
+            vec![
+                ((0, 0), (3, 2)),
+                ((2, 0), (0, 3)),
+                ((3, 2), (0, 0)),
+                ((0, 3), (2, 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_is_covered() {
+        let rects = line_rects((0.0, 0.0), (10.0, 5.0), 2);
+
+        assert_eq!(rects.len(), 11);
+        assert_eq!(rects[0], Rect::new(-1, -1, 2, 2));
+        assert_eq!(rects[10], Rect::new(9, 4, 2, 2));
+    }
+
+    #[test]
+    fn axes_are_drawn() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(1)
+            .set_background(Color::RGB(0, 0, 0));
+
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0[[2, 2, 0]] = Block::Rock;
+
+        let plain = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        let rconfig = rconfig.set_axes(true);
+        let with_axes = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+
+        let count = |pixels: &[u8], color: [u8; 3]| {
+            pixels.chunks(4).filter(|pixel| pixel[..3] == color).count()
+        };
+        for &color in &[[230, 40, 40], [40, 200, 40], [40, 80, 230]] {
+            assert_eq!(count(&plain, color), 0);
+            assert!(count(&with_axes, color) > 0);
+        }
+    }
+}
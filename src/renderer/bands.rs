@@ -0,0 +1,135 @@
+//! Rendering maps a horizontal band at a time, for outputs too large to keep
+//! in memory whole
+
+use super::*;
+
+impl<'a> Renderer<'a> {
+    /// Render an `IsoMap` in horizontal bands, handing each one to `sink` as
+    /// soon as it's done
+    ///
+    /// The output is split into bands `band_height` pixels tall, from the top
+    /// down, and `sink` is called with every band and the y coordinate of its
+    /// top edge in the whole output. The last band is shorter if the height of
+    /// the output isn't a multiple of `band_height`. Only one band is kept in
+    /// memory at a time, so this can render maps whose whole output would be
+    /// too large for a single surface, for example to write it out with an
+    /// encoder that takes one row at a time. Each band still has to fit in a
+    /// surface, and the output can be at most `i32::MAX` pixels along each
+    /// side, otherwise
+    /// [`RendererError::OutputTooLarge`](enum.RendererError.html#variant.OutputTooLarge)
+    /// is returned.
+    ///
+    /// Put back together, the bands are the same image that
+    /// [`render_map`](#method.render_map) makes, since tiles which cross the
+    /// edge of a band are drawn into both bands. The only exceptions are the
+    /// [legend](#method.set_legend) and the [axes](#method.set_axes), which
+    /// aren't drawn at all. A `band_height` of 0 is taken to be 1.
+    pub fn render_bands<F: FnMut(&Surface, u32)>(
+        &self,
+        isomap: &IsoMap,
+        band_height: u32,
+        sink: F,
+    ) -> Result<(), RendererError> {
+        match self.seed {
+            Some(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                self.render_bands_with_rng(isomap, band_height, sink, &mut rng)
+            }
+            None => self.render_bands_with_rng(isomap, band_height, sink, &mut rand::thread_rng()),
+        }
+    }
+
+    fn render_bands_with_rng<F: FnMut(&Surface, u32), R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        band_height: u32,
+        mut sink: F,
+        rng: &mut R,
+    ) -> Result<(), RendererError> {
+        let (width, height) = self.scaled_dimensions(isomap);
+        let band_height = band_height.max(1);
+
+        // Unlike with a whole render, only a band has to fit in a surface
+        let band_bytes = width
+            .saturating_mul(height.min(u64::from(band_height)))
+            .saturating_mul(4);
+        let max_side = i32::MAX as u64;
+        if width > max_side || height > max_side || band_bytes > MAX_OUTPUT_BYTES {
+            return Err(RendererError::OutputTooLarge { width, height });
+        }
+
+        let (surf_width, surf_height, placements) =
+            self.layout_map_sized(isomap, (width as u32, height as u32), rng)?;
+        let shaded = self.depth_shading > 0.0 || self.light != [1.0; 3];
+
+        for top in (0..surf_height).step_by(band_height as usize) {
+            let height = band_height.min(surf_height - top);
+            let bottom = (top + height) as i32;
+
+            let mut band = self.new_output_surface(surf_width, height)?;
+            band.fill_rect(None, self.background)?;
+
+            // Tiles are moved up so that the top of the band is at 0, and
+            // anything outside of it is clipped when drawing
+            let in_band = placements
+                .iter()
+                .filter(|&&(_, dest, _, _, _)| dest.y() < bottom && dest.bottom() > top as i32)
+                .map(|&(tile, mut dest, brightness, z, phase)| {
+                    dest.offset(0, -(top as i32));
+                    (tile, dest, brightness, z, phase)
+                });
+
+            if shaded {
+                let mut ignore = |_| ();
+                let mut progress = FloorProgress::new(isomap.dim_z(), &mut ignore);
+                let clock = Clock::Frame(0);
+                blit_shaded(in_band.collect(), clock, self.light, &mut band, &mut progress)?;
+            } else {
+                for (tile_sprite, tile_dest, _, _, _) in in_band {
+                    for (src, dest, _) in tile_sprite.parts(0, tile_dest, 255, self.light) {
+                        blit_tile(&tile_sprite.sheet, src, &mut band, dest)?;
+                    }
+                }
+            }
+
+            sink(&band, top);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::solid_renderer;
+
+    #[test]
+    fn bands_make_whole_render() {
+        let renderer = || solid_renderer().with_seed(3);
+
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[1, 2, 1]] = Block::Grass;
+        iso_map.0[[3, 3, 3]] = Block::Snow;
+
+        for rconfig in &[renderer(), renderer().set_light(1.0, 0.8, 0.6)] {
+            let whole = rconfig.render_map(&iso_map).unwrap();
+
+            // 10 pixels cuts through most tiles
+            let mut offsets = Vec::new();
+            let mut pixels = Vec::new();
+            rconfig
+                .render_bands(&iso_map, 10, |band, top| {
+                    assert_eq!(band.width(), whole.width());
+                    offsets.push(top);
+                    pixels.extend(surface_to_rgba(band));
+                })
+                .unwrap();
+
+            let expected: Vec<u32> = (0..whole.height()).step_by(10).collect();
+            assert_eq!(offsets, expected);
+            assert_eq!(pixels, surface_to_rgba(&whole));
+        }
+    }
+}
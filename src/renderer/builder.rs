@@ -0,0 +1,126 @@
+//! Building renderers out of surfaces in code, without a TOML configuration
+
+use super::*;
+
+/// Builder for a [`Renderer`](struct.Renderer.html) with tiles from surfaces
+/// provided in code
+///
+/// Every block other than air needs at least one tile, just as with a TOML
+/// configuration. Blocks can have several tiles, which are picked at random.
+///
+/// ## Example use
+/// ```
+/// extern crate cubeglobe;
+/// extern crate enum_iterator;
+/// extern crate sdl2;
+///
+/// use cubeglobe::map::Block;
+/// use cubeglobe::renderer::{RendererBuilder, Surface};
+/// use enum_iterator::IntoEnumIterator;
+/// use sdl2::pixels::PixelFormatEnum;
+/// use sdl2::rect::Rect;
+/// use std::rc::Rc;
+///
+/// # fn main() {
+/// let tile = Rect::new(0, 0, 24, 26);
+/// let rock = Surface::new(24, 26, PixelFormatEnum::RGBA32).unwrap();
+///
+/// // A sheet can be shared between blocks
+/// let sheet = Rc::new(Surface::new(24, 26, PixelFormatEnum::RGBA32).unwrap());
+///
+/// let renderer = Block::into_enum_iter()
+///     .filter(|&block| block != Block::Air && block != Block::Rock)
+///     .fold(RendererBuilder::new(24, 26), |builder, block| {
+///         builder.add_tile(block, Rc::clone(&sheet), tile)
+///     })
+///     .add_tile(Block::Rock, rock, tile)
+///     .build()
+///     .unwrap();
+/// # }
+/// ```
+pub struct RendererBuilder<'a> {
+    width: u32,
+    height: u32,
+    tiles: HashMap<Block, Vec<Tile<'a>>>,
+}
+
+impl<'a> RendererBuilder<'a> {
+    /// Start building a renderer for tiles `width` by `height` pixels large
+    pub fn new(width: u32, height: u32) -> RendererBuilder<'a> {
+        RendererBuilder {
+            width,
+            height,
+            tiles: HashMap::new(),
+        }
+    }
+
+    /// Add a tile for `block`, which is the rectangle `pos` in `sheet`
+    ///
+    /// `sheet` can be a `Surface`, or an `Rc` of one to share it between
+    /// tiles. Tiles are drawn with the blend mode the surface has, which for
+    /// surfaces with an alpha channel is usually alpha blending.
+    pub fn add_tile<S: Into<Rc<Surface<'a>>>>(
+        mut self,
+        block: Block,
+        sheet: S,
+        pos: Rect,
+    ) -> RendererBuilder<'a> {
+        self.tiles.entry(block).or_default().push(Tile {
+            sheet: sheet.into(),
+            pos,
+            weight: 1,
+            frames: 1,
+            stride: pos.width() as i32,
+            autotile: None,
+            faces: None,
+            top_offset: 0,
+        });
+
+        self
+    }
+
+    /// Build the renderer
+    ///
+    /// Returns an error if a block other than air has no tiles, or if the tile
+    /// width isn't a multiple of 4.
+    pub fn build(self) -> Result<Renderer<'a>, ConfigLoadError> {
+        Renderer::from_tiles(self.width, self.height, self.tiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_block() {
+        match RendererBuilder::new(24, 26).build() {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::MissingBlock(Block::Rock)),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn build_and_render() {
+        let sheet = Rc::new(Surface::new(48, 26, PixelFormatEnum::RGBA32).unwrap());
+        let builder = Block::into_enum_iter()
+            .filter(|&block| block != Block::Air)
+            .fold(RendererBuilder::new(24, 26), |builder, block| {
+                builder.add_tile(block, Rc::clone(&sheet), Rect::new(0, 0, 24, 26))
+            });
+
+        let rconfig = builder
+            .add_tile(Block::Rock, Rc::clone(&sheet), Rect::new(24, 0, 24, 26))
+            .build()
+            .unwrap();
+
+        assert_eq!(rconfig.tile_counts()[&Block::Rock], 2);
+        assert_eq!(rconfig.tile_counts()[&Block::Water], 1);
+
+        let iso_map = IsoMap::new_empty(2);
+        assert_eq!(
+            rconfig.render_map(&iso_map).unwrap().size(),
+            rconfig.output_dimensions(&iso_map)
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Rendering many maps in a row without allocating a surface for each
+
+use super::*;
+
+/// A renderer along with a surface it renders into, which is kept between
+/// renders
+///
+/// Made with [`Renderer::render_context`](struct.Renderer.html#method.render_context).
+/// Rendering a small map takes little enough time that making a new surface
+/// for the output every time is a large part of it, so when rendering lots of
+/// maps in a row, such as for a live preview, the surface is worth reusing.
+/// The surface grows to fit the largest map rendered so far, and is never
+/// shrunk.
+///
+/// ## Example use
+/// ```no_run
+/// use cubeglobe::map::generator::{Generator, TerGenTwo};
+/// use cubeglobe::renderer::Renderer;
+/// use std::path::Path;
+///
+/// let rconfig = Renderer::from_config_file(Path::new("tiles.toml")).unwrap();
+/// let mut context = rconfig.render_context();
+///
+/// for seed in 0..100 {
+///     let iso_map = TerGenTwo::new().set_len(16).set_seed(seed).generate();
+///     let surface = context.render(&iso_map).unwrap();
+///     // The map is in the upper left corner of `surface`
+/// }
+/// ```
+pub struct RenderContext<'r, 'a: 'r> {
+    renderer: &'r Renderer<'a>,
+    scratch: Option<Surface<'static>>,
+}
+
+impl<'r, 'a> RenderContext<'r, 'a> {
+    /// Render an `IsoMap` into the context's surface
+    ///
+    /// The map is drawn in the upper left corner of the surface, the same way
+    /// as with
+    /// [`Renderer::render_map_into`](struct.Renderer.html#method.render_map_into),
+    /// and takes up
+    /// [`output_dimensions`](struct.Renderer.html#method.output_dimensions)
+    /// of it. If the surface is larger than that, because a larger map was
+    /// rendered before, the rest of it is background.
+    pub fn render(&mut self, isomap: &IsoMap) -> Result<&Surface<'static>, RendererError> {
+        let (width, height) = self.renderer.checked_dimensions(isomap)?;
+
+        let fits = match self.scratch {
+            Some(ref scratch) => scratch.width() >= width && scratch.height() >= height,
+            None => false,
+        };
+
+        if !fits {
+            let (old_width, old_height) = match self.scratch {
+                Some(ref scratch) => scratch.size(),
+                None => (0, 0),
+            };
+
+            self.scratch = Some(
+                self.renderer
+                    .new_output_surface(width.max(old_width), height.max(old_height))?,
+            );
+        }
+
+        let scratch = self.scratch.as_mut().expect("scratch surface was just made");
+        self.renderer.render_map_into(isomap, scratch)?;
+
+        Ok(scratch)
+    }
+}
+
+impl<'a> Renderer<'a> {
+    /// Get a context for rendering lots of maps in a row, which reuses its
+    /// output surface between renders
+    ///
+    /// See [`RenderContext`](struct.RenderContext.html) for details.
+    pub fn render_context(&self) -> RenderContext<'_, 'a> {
+        RenderContext {
+            renderer: self,
+            scratch: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::solid_renderer;
+
+    #[test]
+    fn scratch_grows() {
+        let rconfig = solid_renderer().with_seed(2);
+        let mut context = rconfig.render_context();
+
+        let mut small = IsoMap::new_empty(2);
+        small.0[[0, 0, 0]] = Block::Rock;
+        let mut large = IsoMap::new_empty(4);
+        large.0[[1, 2, 3]] = Block::Grass;
+
+        let expected = |isomap: &IsoMap| surface_to_rgba(&rconfig.render_map(isomap).unwrap());
+
+        let surface = context.render(&small).unwrap();
+        assert_eq!(surface.size(), rconfig.output_dimensions(&small));
+        assert_eq!(surface_to_rgba(surface), expected(&small));
+
+        let surface = context.render(&large).unwrap();
+        assert_eq!(surface.size(), rconfig.output_dimensions(&large));
+        assert_eq!(surface_to_rgba(surface), expected(&large));
+
+        // Going back to a smaller map keeps the larger surface
+        let surface = context.render(&small).unwrap();
+        assert_eq!(surface.size(), rconfig.output_dimensions(&large));
+    }
+}
@@ -2,6 +2,8 @@
 
 use super::*;
 
+use std::io;
+
 use failure::{Backtrace, Context, Fail};
 
 
@@ -15,24 +17,69 @@ impl ConfigLoadError {
     pub fn kind(&self) -> &ConfigLoadErrorKind {
         &self.inner.get_context()
     }
+
+    /// Get the kind of the error, to match on or pass along with context of
+    /// its own
+    pub fn into_kind(self) -> ConfigLoadErrorKind {
+        self.inner.get_context().clone()
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Fail)]
 pub enum ConfigLoadErrorKind {
-    #[fail(display = "A problem with parsing the provided TOML")]
-    TomlParseError,
+    #[fail(display = "A problem with reading the config file")]
+    FileReadError,
 
-    /// This variant wraps the `String` returned from the SDL2 library
-    #[fail(display = "A problem with loading and processing images with SDL")]
-    SDLError(String),
+    /// The config isn't valid TOML, or is missing something. `filename` is
+    /// the config file, if it was loaded from one.
+    #[fail(display = "A problem with parsing the provided TOML")]
+    TomlParseError { filename: Option<String> },
+
+    /// The tile width isn't a positive multiple of 4, which the isometric
+    /// projection needs to line tiles up exactly
+    #[fail(display = "The tile width is {}, which isn't a multiple of 4", _0)]
+    BadTileWidth(u32),
+
+    /// This variant wraps the `String` returned from the SDL2 library, along
+    /// with the spritesheet being loaded or processed, if the error is about
+    /// one
+    #[fail(display = "A problem with loading and processing images with SDL: {}", message)]
+    SDLError {
+        filename: Option<String>,
+        message: String,
+    },
 
     #[fail(display = "One of the required blocks had no tiles supplied")]
     MissingBlock(Block),
+
+    /// No spritesheet was given for a file in the config
+    #[fail(display = "No spritesheet was supplied for {}", _0)]
+    MissingSheet(String),
+
+    #[fail(display = "A tile for {:?} extends past the edges of {}", block, filename)]
+    TileOutOfBounds { filename: String, block: Block },
+
+    #[fail(display = "A face of a tile for {:?} extends past the edges of the tile", _0)]
+    FaceOutOfBounds(Block),
+
+    #[fail(display = "The top face of a tile for {:?} starts below the tile", _0)]
+    TopOffsetOutOfBounds(Block),
 }
 
 impl ConfigLoadErrorKind {
     pub fn from_sdl_string_err(s: String) -> ConfigLoadErrorKind {
-        ConfigLoadErrorKind::SDLError(s)
+        ConfigLoadErrorKind::SDLError {
+            filename: None,
+            message: s,
+        }
+    }
+
+    /// Wrap an error SDL returned for the spritesheet `filename`
+    pub fn from_sdl_file_err(filename: &str, s: String) -> ConfigLoadErrorKind {
+        ConfigLoadErrorKind::SDLError {
+            filename: Some(filename.to_string()),
+            message: s,
+        }
     }
 }
 
@@ -48,7 +95,19 @@ impl Fail for ConfigLoadError {
 
 impl Display for ConfigLoadError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        Display::fmt(&self.inner, f)
+        Display::fmt(&self.inner, f)?;
+
+        // Other kinds with a filename already have it in their message
+        match self.kind() {
+            ConfigLoadErrorKind::SDLError {
+                filename: Some(filename),
+                ..
+            }
+            | ConfigLoadErrorKind::TomlParseError {
+                filename: Some(filename),
+            } => write!(f, " (in {})", filename),
+            _ => Ok(()),
+        }
     }
 }
 
@@ -68,11 +127,48 @@ impl From<Context<ConfigLoadErrorKind>> for ConfigLoadError {
 
 /// An error with rendering an `IsoMap`
 #[derive(Fail, Debug)]
-#[fail(display = "SDL returned an error: {}", sdl_err)]
-pub struct RendererError{sdl_err: String}
+pub enum RendererError {
+    /// This variant wraps the `String` returned from the SDL2 library
+    #[fail(display = "SDL returned an error: {}", _0)]
+    SDLError(String),
+
+    #[fail(display = "The renderer has no tiles for a block in the map")]
+    MissingTile(Block),
+
+    #[fail(
+        display = "The target surface is {}x{}, but the render needs {}x{}",
+        target_width, target_height, width, height
+    )]
+    TargetTooSmall {
+        width: u32,
+        height: u32,
+        target_width: u32,
+        target_height: u32,
+    },
+
+    /// The output for a map would be larger than an SDL surface can be, so
+    /// rendering it wasn't attempted
+    #[fail(
+        display = "The render would be {}x{}, which is too large for a surface",
+        width, height
+    )]
+    OutputTooLarge { width: u64, height: u64 },
+
+    #[fail(display = "There were no frames to encode")]
+    NoFrames,
+
+    #[fail(display = "A problem with writing the output: {}", _0)]
+    IoError(#[cause] io::Error),
+}
+
+impl From<io::Error> for RendererError {
+    fn from(e: io::Error) -> RendererError {
+        RendererError::IoError(e)
+    }
+}
 
 impl From<String> for RendererError {
    fn from(s: String) -> RendererError{
-        RendererError{ sdl_err: s }
+        RendererError::SDLError(s)
     }
 }
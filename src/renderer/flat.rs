@@ -0,0 +1,141 @@
+//! Rendering a single floor of a map as a flat grid of tiles, seen from above
+
+use super::*;
+
+impl<'a> Renderer<'a> {
+    /// Render floor `z` of an `IsoMap` as a flat grid of tiles, seen from
+    /// above
+    ///
+    /// The block at `x`, `y` is drawn in a cell at `x * width`, `y * height`,
+    /// where `width` and `height` are the size of a tile in the config, with
+    /// no isometric offset, so the image is `width` times the length of the
+    /// map along x, by `height` times its length along y. This is meant for
+    /// things like minimaps in editors.
+    ///
+    /// Tiles with [faces](index.html#lighting) have only their top face drawn,
+    /// stretched over the whole cell. Other tiles are drawn whole, squeezed
+    /// into the cell if they're a different size. The render is scaled, and
+    /// uses the background, the policy for missing tiles and the way variants
+    /// are picked, but it's always seen from the north, and none of the other
+    /// settings apply. Floors above the top of the map render as just the
+    /// background.
+    pub fn render_floor_flat<'b>(
+        &self,
+        isomap: &IsoMap,
+        z: usize,
+    ) -> Result<Surface<'b>, RendererError> {
+        match self.seed {
+            Some(seed) => {
+                self.render_floor_flat_with_rng(isomap, z, &mut StdRng::seed_from_u64(seed))
+            }
+            None => self.render_floor_flat_with_rng(isomap, z, &mut rand::thread_rng()),
+        }
+    }
+
+    fn render_floor_flat_with_rng<'b, R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        z: usize,
+        rng: &mut R,
+    ) -> Result<Surface<'b>, RendererError> {
+        let (len_x, len_y, len_z) = isomap.shape();
+        let cell_width = self.width * self.scale;
+        let cell_height = self.height * self.scale;
+
+        let width = len_x as u64 * u64::from(cell_width);
+        let height = len_y as u64 * u64::from(cell_height);
+        if width.saturating_mul(height).saturating_mul(4) > MAX_OUTPUT_BYTES {
+            return Err(RendererError::OutputTooLarge { width, height });
+        }
+
+        let mut out = self.new_output_surface(width as u32, height as u32)?;
+        out.fill_rect(None, self.background)?;
+
+        if z >= len_z {
+            return Ok(out);
+        }
+
+        let map = isomap.0.view();
+        let hash_seed = self.hash_seed(rng);
+
+        for ((x, y), block) in isomap.0.index_axis(Axis(2), z).indexed_iter() {
+            if block == &Block::Air {
+                continue;
+            }
+
+            let drawn = match self.drawn_block(*block)? {
+                Some(block) => block,
+                None => continue,
+            };
+
+            let edges = edge_mask(&map, x, y, z);
+            let tile = self.pick_variant(&drawn, edges, hash_seed, (x, y, z), rng)?;
+
+            let src = tile.frame_pos(0);
+            let src = match tile.faces {
+                Some([top, _, _]) => Rect::new(
+                    src.x() + top.x(),
+                    src.y() + top.y(),
+                    top.width(),
+                    top.height(),
+                ),
+                None => src,
+            };
+            let dest = Rect::new(
+                x as i32 * cell_width as i32,
+                y as i32 * cell_height as i32,
+                cell_width,
+                cell_height,
+            );
+
+            blit_tile(&tile.sheet, src, &mut out, dest)?;
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::solid_renderer;
+
+    #[test]
+    fn flat_grid() {
+        let rconfig = solid_renderer().set_background(Color::RGB(1, 2, 3));
+        let mut iso_map = IsoMap::new_empty_dims(2, 3, 2);
+        iso_map.0[[0, 0, 0]] = Block::Grass;
+        iso_map.0[[1, 2, 0]] = Block::Rock;
+        iso_map.0[[1, 0, 1]] = Block::Snow;
+
+        let surf = rconfig.render_floor_flat(&iso_map, 0).unwrap();
+        assert_eq!(surf.size(), (48, 78));
+
+        let buffer = surface_to_rgba(&surf);
+        let pixel = |x: u32, y: u32| {
+            let offset = ((y * surf.width() + x) * 4) as usize;
+            buffer[offset..offset + 3].to_vec()
+        };
+        let rgb = |color: Color| vec![color.r, color.g, color.b];
+
+        // The middle of the top face of every cube, with no offset between
+        // rows
+        assert_eq!(pixel(12, 6), rgb(Block::Grass.default_color()));
+        assert_eq!(pixel(36, 58), rgb(Block::Rock.default_color()));
+        assert_eq!(pixel(36, 6), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn flat_above_map() {
+        let rconfig = solid_renderer().set_background(Color::RGB(1, 2, 3));
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 1]] = Block::Grass;
+
+        let surf = rconfig.render_floor_flat(&iso_map, 5).unwrap();
+        assert_eq!(surf.size(), (48, 52));
+
+        assert!(surface_to_rgba(&surf)
+            .chunks(4)
+            .all(|pixel| pixel[..3] == [1, 2, 3]));
+    }
+}
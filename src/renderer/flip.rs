@@ -0,0 +1,224 @@
+//! Mirrored copies of tiles, which the config can allow for some blocks to
+//! make them look less repetitive
+
+use super::*;
+
+impl<'a> Tile<'a> {
+    /// Get this tile flipped horizontally, out of `sheet`, which has to be
+    /// this tile's sheet mirrored
+    ///
+    /// The left and right faces swap places along with everything else, so
+    /// that each is still lit as the side it's drawn on, and so do the edges
+    /// of autotiled tiles.
+    fn mirrored(&self, sheet: Rc<Surface<'a>>) -> Tile<'a> {
+        let width = self.pos.width();
+        let sheet_x = sheet.width() as i32 - self.pos.x() - width as i32;
+        let flip = |face: Rect| {
+            let x = width as i32 - face.x() - face.width() as i32;
+            Rect::new(x, face.y(), face.width(), face.height())
+        };
+
+        Tile {
+            sheet,
+            pos: Rect::new(sheet_x, self.pos.y(), width, self.pos.height()),
+            weight: self.weight,
+            frames: self.frames,
+            // Frames are mirrored too, so they go the other way
+            stride: -self.stride,
+            autotile: self.autotile.map(mirror_edges),
+            faces: self
+                .faces
+                .map(|[top, left, right]| [flip(top), flip(right), flip(left)]),
+            top_offset: self.top_offset,
+        }
+    }
+}
+
+/// Add a mirrored copy of every tile of each block in `blocks`
+///
+/// Copies have the same weight as the tiles they're made from, so a block is
+/// drawn flipped about half the time. Each sheet is only mirrored once, no
+/// matter how many tiles come from it.
+pub(super) fn add_flipped_tiles(
+    tiles: &mut HashMap<Block, Vec<Tile>>,
+    blocks: &[Block],
+) -> Result<(), ConfigLoadError> {
+    let mut sheets: Vec<(Rc<Surface>, Rc<Surface>)> = Vec::new();
+    let mut done: Vec<Block> = Vec::new();
+
+    for block in blocks {
+        if done.contains(block) {
+            continue;
+        }
+        done.push(*block);
+
+        let block_tiles = match tiles.get_mut(block) {
+            Some(block_tiles) => block_tiles,
+            None => continue,
+        };
+
+        let mut flipped = Vec::with_capacity(block_tiles.len());
+        for tile in block_tiles.iter() {
+            let existing = sheets
+                .iter()
+                .find(|(sheet, _)| Rc::ptr_eq(sheet, &tile.sheet))
+                .map(|(_, mirrored)| Rc::clone(mirrored));
+
+            let mirrored = match existing {
+                Some(mirrored) => mirrored,
+                None => {
+                    let mirrored = Rc::new(
+                        mirror_sheet(&tile.sheet)
+                            .map_err(ConfigLoadErrorKind::from_sdl_string_err)?,
+                    );
+                    sheets.push((Rc::clone(&tile.sheet), Rc::clone(&mirrored)));
+                    mirrored
+                }
+            };
+
+            flipped.push(tile.mirrored(mirrored));
+        }
+
+        block_tiles.extend(flipped);
+    }
+
+    Ok(())
+}
+
+/// Make a copy of `sheet` flipped horizontally
+///
+/// Surfaces can't be blitted flipped, so flipped tiles are copied out of a
+/// mirrored sheet instead. The copy is always RGBA32, with the same blending
+/// and color key as `sheet`.
+fn mirror_sheet(sheet: &Surface) -> Result<Surface<'static>, String> {
+    let converted = sheet.convert_format(PixelFormatEnum::RGBA32)?;
+    let (width, height) = converted.size();
+    let mut mirrored = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+
+    let src_pitch = converted.pitch() as usize;
+    let dst_pitch = mirrored.pitch() as usize;
+    converted.with_lock(|src| {
+        mirrored.with_lock_mut(|dst| {
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let from = y * src_pitch + x * 4;
+                    let to = y * dst_pitch + (width as usize - 1 - x) * 4;
+                    dst[to..to + 4].copy_from_slice(&src[from..from + 4]);
+                }
+            }
+        })
+    });
+
+    mirrored.set_blend_mode(sheet.blend_mode())?;
+    if let Ok(key) = converted.color_key() {
+        mirrored.set_color_key(true, key)?;
+    }
+
+    Ok(mirrored)
+}
+
+/// Get the edge mask of a tile after flipping it horizontally, which swaps
+/// north with west and east with south
+fn mirror_edges(mask: u8) -> u8 {
+    let swaps = [
+        (Edge::North, Edge::West),
+        (Edge::East, Edge::South),
+        (Edge::South, Edge::East),
+        (Edge::West, Edge::North),
+    ];
+
+    swaps
+        .iter()
+        .filter(|(from, _)| mask & from.bit() != 0)
+        .fold(0, |flipped, (_, to)| flipped | to.bit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::test_config_path;
+
+    #[test]
+    fn edges_mirror() {
+        assert_eq!(mirror_edges(0), 0);
+        assert_eq!(mirror_edges(Edge::North.bit()), Edge::West.bit());
+        assert_eq!(mirror_edges(Edge::East.bit()), Edge::South.bit());
+        assert_eq!(
+            mirror_edges(Edge::North.bit() | Edge::East.bit()),
+            Edge::West.bit() | Edge::South.bit()
+        );
+        assert_eq!(mirror_edges(15), 15);
+
+        for mask in 0..16 {
+            assert_eq!(mirror_edges(mirror_edges(mask)), mask);
+        }
+    }
+
+    #[test]
+    fn flipped_tiles_mirrored() {
+        let mut sheet = Surface::new(4, 1, PixelFormatEnum::RGBA32).unwrap();
+        sheet.with_lock_mut(|pixels| {
+            for (i, pixel) in pixels.chunks_mut(4).enumerate() {
+                pixel.copy_from_slice(&[i as u8, 0, 0, 255]);
+            }
+        });
+
+        let mirrored = mirror_sheet(&sheet).unwrap();
+        assert_eq!(mirrored.size(), (4, 1));
+        let pixels = surface_to_rgba(&mirrored);
+        let reds: Vec<u8> = pixels.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(reds, vec![3, 2, 1, 0]);
+
+        let tile = Tile {
+            sheet: Rc::new(sheet),
+            pos: Rect::new(0, 0, 3, 1),
+            weight: 2,
+            frames: 1,
+            stride: 3,
+            autotile: None,
+            faces: Some([
+                Rect::new(0, 0, 3, 1),
+                Rect::new(0, 0, 1, 1),
+                Rect::new(1, 0, 2, 1),
+            ]),
+            top_offset: 0,
+        };
+        let flipped = tile.mirrored(Rc::new(mirrored));
+
+        assert_eq!(flipped.pos, Rect::new(1, 0, 3, 1));
+        assert_eq!(flipped.stride, -3);
+        assert_eq!(flipped.weight, 2);
+        assert_eq!(
+            flipped.faces,
+            Some([Rect::new(0, 0, 3, 1), Rect::new(0, 0, 2, 1), Rect::new(2, 0, 1, 1)])
+        );
+    }
+
+    #[test]
+    fn allow_flip_config() {
+        let path = test_config_path();
+        let tiles_toml = fs::read_to_string(&path).unwrap();
+        let config = format!("allow_flip = [\"Rock\", \"Soil\"]\n{}", tiles_toml);
+        let load = |flip: bool| {
+            if flip {
+                Renderer::from_config_reader(config.as_bytes(), path.parent().unwrap()).unwrap()
+            } else {
+                Renderer::from_config_file(&path).unwrap()
+            }
+        };
+
+        let (plain_counts, counts) = (load(false).tile_counts(), load(true).tile_counts());
+        assert_eq!(counts[&Block::Rock], plain_counts[&Block::Rock] * 2);
+        assert_eq!(counts[&Block::Soil], plain_counts[&Block::Soil] * 2);
+        assert_eq!(counts[&Block::Grass], plain_counts[&Block::Grass]);
+
+        // Rendering picks flipped tiles too, so some renders differ
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        let render = |flip: bool, seed: u64| {
+            surface_to_rgba(&load(flip).with_seed(seed).render_map(&iso_map).unwrap())
+        };
+        let differs = (0..8).any(|seed| render(false, seed) != render(true, seed));
+        assert!(differs);
+    }
+}
@@ -0,0 +1,242 @@
+//! Legend listing the blocks in a render, drawn in a corner of the output
+
+use super::*;
+
+/// Width of a character of the legend font, in font pixels
+const GLYPH_WIDTH: u32 = 3;
+
+/// Height of a character of the legend font, in font pixels
+pub(super) const GLYPH_HEIGHT: u32 = 5;
+
+/// Size of a font pixel, in output pixels, before scaling
+pub(super) const FONT_PIXEL: u32 = 2;
+
+/// Space between the parts of the legend, in output pixels, before scaling
+const PADDING: u32 = 4;
+
+impl<'a> Renderer<'a> {
+    /// Draw a legend of the blocks in `isomap` in the upper left corner of
+    /// `target`
+    ///
+    /// Every kind of block in the map gets a row with a swatch, which is the
+    /// first of its tiles, followed by its name.
+    pub(super) fn draw_legend(&self, isomap: &IsoMap, target: &mut Surface) -> Result<(), String> {
+        let counts = isomap.block_counts();
+        let scale = self.scale;
+        let padding = (PADDING * scale) as i32;
+        let font_pixel = FONT_PIXEL * scale;
+        let row_height = self.height * scale + padding as u32;
+
+        // Text goes in black or white, whichever stands out on the background
+        let background = self.background;
+        let luma = 299 * u32::from(background.r)
+            + 587 * u32::from(background.g)
+            + 114 * u32::from(background.b);
+        let text_color = if luma > 128_000 {
+            Color::RGB(0, 0, 0)
+        } else {
+            Color::RGB(255, 255, 255)
+        };
+
+        // Named blocks come first, in the order they're declared in, and then
+        // custom blocks by id
+        let mut blocks: Vec<Block> = counts
+            .iter()
+            .filter(|&(block, &count)| block != &Block::Air && count > 0)
+            .map(|(&block, _)| block)
+            .collect();
+        blocks.sort_by_key(|&block| match block {
+            Block::Custom(id) => (1, usize::from(id)),
+            named => (0, Block::into_enum_iter().position(|b| b == named).unwrap_or(0)),
+        });
+
+        for (row, block) in blocks.into_iter().enumerate() {
+            let y = padding + row as i32 * row_height as i32;
+
+            if let Some(tile) = self.tiles.get(&block).and_then(|tiles| tiles.first()) {
+                let dest = Rect::new(padding, y, self.width * scale, self.height * scale);
+                blit_tile(&tile.sheet, tile.frame_pos(0), target, dest)?;
+            }
+
+            let text_x = padding * 2 + (self.width * scale) as i32;
+            let text_y =
+                y + ((self.height * scale).saturating_sub(GLYPH_HEIGHT * font_pixel) / 2) as i32;
+            let name = block.to_string().to_uppercase();
+
+            let rects = text_pixels(&name)
+                .map(|(x, y)| {
+                    Rect::new(
+                        text_x + (x * font_pixel) as i32,
+                        text_y + (y * font_pixel) as i32,
+                        font_pixel,
+                        font_pixel,
+                    )
+                })
+                .collect::<Vec<Rect>>();
+
+            target.fill_rects(&rects, text_color)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the positions of the lit pixels of `text` in the legend font, with one
+/// pixel of space between characters
+///
+/// Characters the font doesn't have are left blank.
+pub(super) fn text_pixels(text: &str) -> impl Iterator<Item = (u32, u32)> + '_ {
+    text.chars().enumerate().flat_map(|(i, c)| {
+        let rows = glyph(c).unwrap_or([0; GLYPH_HEIGHT as usize]);
+        let left = i as u32 * (GLYPH_WIDTH + 1);
+
+        (0..GLYPH_HEIGHT).flat_map(move |y| {
+            (0..GLYPH_WIDTH)
+                .filter(move |x| rows[y as usize] & (0b100 >> x) != 0)
+                .map(move |x| (left + x, y))
+        })
+    })
+}
+
+/// Get the rows of a character of the legend font, top to bottom, with the
+/// leftmost pixel in the highest bit
+///
+/// The font only has the characters needed for the names of blocks and axes.
+fn glyph(c: char) -> Option<[u8; GLYPH_HEIGHT as usize]> {
+    Some(match c {
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b110, 0b101, 0b010],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b010, 0b101, 0b010, 0b101, 0b010],
+        '9' => [0b010, 0b101, 0b011, 0b001, 0b110],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::test_config_path;
+
+    #[test]
+    fn font_covers_block_names() {
+        let custom = (0..=255).map(Block::Custom);
+
+        for block in Block::into_enum_iter().chain(custom) {
+            let name = block.to_string().to_uppercase();
+
+            for c in name.chars() {
+                assert!(glyph(c).is_some(), "no glyph for {} in {}", c, name);
+            }
+        }
+    }
+
+    #[test]
+    fn text_layout() {
+        let pixels: Vec<(u32, u32)> = text_pixels("TI").collect();
+
+        // The top bar of each letter, with a gap between them
+        assert!(pixels.contains(&(0, 0)) && pixels.contains(&(2, 0)));
+        assert!(pixels.contains(&(4, 0)) && pixels.contains(&(6, 0)));
+        assert!(!pixels.iter().any(|&(x, _)| x == 3));
+        assert_eq!(pixels.len(), 7 + 9);
+
+        assert_eq!(text_pixels("?").count(), 0);
+    }
+
+    #[test]
+    fn legend_in_corner() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(1);
+
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0[[3, 3, 0]] = Block::Rock;
+        iso_map.0[[2, 3, 0]] = Block::Water;
+
+        let plain = rconfig.render_map(&iso_map).unwrap();
+        let rconfig = rconfig.set_legend(true);
+        let with_legend = rconfig.render_map(&iso_map).unwrap();
+        assert_eq!(plain.size(), with_legend.size());
+
+        // Only the corner with the legend changes, with a row for each block
+        let width = plain.width() as usize;
+        let rows = |surf: &Surface| -> Vec<Vec<u8>> {
+            surface_to_rgba(surf)
+                .chunks(width * 4)
+                .map(|row| row.to_vec())
+                .collect()
+        };
+        let (plain, with_legend) = (rows(&plain), rows(&with_legend));
+        let changed: Vec<usize> = (0..plain.len())
+            .filter(|&y| plain[y] != with_legend[y])
+            .collect();
+
+        let padding = PADDING as usize;
+        let row_height = rconfig.height as usize + padding;
+        assert_eq!(changed.first(), Some(&padding));
+        assert!(changed.last().unwrap() < &(padding + 2 * row_height));
+        assert!(changed.last().unwrap() >= &(padding + row_height));
+    }
+
+    #[test]
+    fn custom_blocks_listed() {
+        let mut colors: HashMap<Block, Color> = Block::into_enum_iter()
+            .filter(|block| block != &Block::Air)
+            .map(|block| (block, block.default_color()))
+            .collect();
+        colors.insert(Block::Custom(12), Color::RGB(200, 0, 200));
+        let rconfig = Renderer::solid_colors(24, 26, colors)
+            .unwrap()
+            .set_background(Color::RGB(0, 0, 0))
+            .set_legend(true);
+
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0[[3, 3, 0]] = Block::Rock;
+        iso_map.0[[2, 3, 0]] = Block::Custom(12);
+
+        // The custom block gets the second row, with its swatch and its name
+        let surf = rconfig.render_map(&iso_map).unwrap();
+        let width = surf.width() as usize;
+        let pixels = surface_to_rgba(&surf);
+        let pixel = |x: usize, y: usize| pixels[(y * width + x) * 4..][..3].to_vec();
+
+        let padding = PADDING as usize;
+        let row_top = padding + rconfig.height as usize + padding;
+        assert_eq!(pixel(padding + 12, row_top + 6), vec![200, 0, 200]);
+
+        let text_x = padding * 2 + rconfig.width as usize;
+        let text_y = row_top + (rconfig.height - GLYPH_HEIGHT * FONT_PIXEL) as usize / 2;
+        let font_pixel = FONT_PIXEL as usize;
+        for (x, y) in text_pixels("CUSTOM(12)") {
+            let (x, y) = (x as usize * font_pixel, y as usize * font_pixel);
+            assert_eq!(pixel(text_x + x, text_y + y), vec![255, 255, 255]);
+        }
+    }
+}
@@ -11,18 +11,51 @@
 //! Multiple tiles can be present in one file, forming a spritesheet. The TOML
 //! can specify multiple files to load.
 //!
+//! Sheets can have tiles of a different size than the rest, such as tall cliff
+//! tiles whose sides cover the blocks below them. A tile is always centred
+//! horizontally on its block, with the top edge of the tile at the top of the
+//! block's top face, so any extra height hangs down over what's in front of
+//! and below the block. The output is made large enough to fit the largest
+//! tile.
+//!
+//! Tiles with something standing on the block, such as a tree, can set
+//! `top_offset` to the number of pixels between the top edge of the tile and
+//! the top of the cube's top face. The tile is then drawn that much higher, so
+//! that the cube still lines up with the rest of the map.
+//!
 //! ## TOML configuration file
 //! The configuration file specifies what tiles are in what sprite sheets. For
 //! example:
 //!
 //! ```TOML
-//! # Width and height of an individual tile in pixels
+//! # Width and height of an individual tile in pixels. The width has to be a
+//! # multiple of 4, so that tiles line up exactly.
 //! width = 24
 //! height = 24
 //!
+//! # Directory containing the spritesheets. When loading with
+//! # `from_config_file`, a relative path is relative to the config file. This
+//! # is optional, and it's ignored by `from_config_str_with_base` and
+//! # `from_config_str_with_sheets`.
+//! base_path = "."
+//!
+//! # A color to treat as transparent in every spritesheet, for sheets without
+//! # an alpha channel. This is optional, and without it, only the alpha
+//! # channel makes pixels transparent.
+//! color_key = [255, 0, 255]
+//!
 //! [[files]]
 //! filename = "cubes.png"
 //!
+//! # The width and height of the tiles in this file, if they're different from
+//! # the ones above. Both are optional.
+//! width = 24
+//! height = 48
+//!
+//! # The transparent color of this file, if it's different from the one
+//! # above. This is optional as well.
+//! color_key = [0, 255, 0]
+//!
 //!     [[files.tiles]]
 //!     kind = "Rock"
 //!
@@ -32,48 +65,256 @@
 //!     kind = "Rock"
 //!     x = 25
 //!     y = 0
+//!
+//!     # Weights are optional, and assumed to be 1 if not specified. A tile
+//!     # with weight 3 is picked three times as often as one with weight 1.
+//!     weight = 3
+//!
+//!     # Pixels above the top face of the cube, for tiles taller than a cube
+//!     # which stick up above their block. This is optional, and assumed to be
+//!     # 0, with the top face against the top of the tile.
+//!     top_offset = 0
+//!
+//!     # Animated tiles have several frames, laid out left to right. The
+//!     # stride is the distance between frames in pixels, and is assumed to be
+//!     # the tile width if not specified.
+//!     [[files.tiles]]
+//!     kind = "Water"
+//!     x = 0
+//!     y = 26
+//!     frames = 4
+//!     stride = 24
+//!
+//!     # Autotiled variants are only used for blocks with exactly the listed
+//!     # edges, that is, sides with nothing next to them on the same floor.
+//!     # Edges are named as seen on screen, with north up and to the right.
+//!     # Blocks with no matching autotiled variant use the plain ones, and an
+//!     # empty list is a variant for blocks with no edges at all.
+//!     [[files.tiles]]
+//!     kind = "Grass"
+//!     x = 0
+//!     y = 78
+//!     autotile = ["edge_n", "edge_e"]
+//!
+//!     # Many variants of one block can be declared at once as a grid. Tiles
+//!     # are laid out left to right in rows of `columns`, starting at x, y.
+//!     # Only `kind` and `count` are required; by default, there's a single
+//!     # row, and the strides are the tile width and height.
+//!     [[files.grid]]
+//!     kind = "Rock"
+//!     x = 0
+//!     y = 52
+//!     count = 16
+//!     columns = 4
+//!     stride_x = 24
+//!     stride_y = 26
+//! ```
+//!
+//! ## Custom blocks
+//! Tiles for [custom blocks](../map/enum.Block.html#variant.Custom) are
+//! given by their id. Every named block needs a tile when the config is
+//! loaded, but custom blocks are only checked when rendering: a map with a
+//! custom block the config has no tiles for fails to render with
+//! [`RendererError::MissingTile`](enum.RendererError.html#variant.MissingTile),
+//! unless the renderer is told to do otherwise with
+//! [`set_missing_policy`](struct.Renderer.html#method.set_missing_policy).
+//!
+//! ```TOML
+//!     [[files.tiles]]
+//!     kind = { Custom = 3 }
+//!     x = 0
+//!     y = 104
+//! ```
+//!
+//! ## Transparency
+//! Spritesheets can have an alpha channel, and every tile is alpha blended
+//! over whatever is behind it, so the parts of a tile outside of the cube
+//! should be transparent, and edges can be partially transparent.
+//!
+//! ## Lighting
+//! Tiles can be split into the top, left and right faces of the cube, each a
+//! rectangle relative to the upper left corner of the tile. Every face is then
+//! drawn with its own brightness, set with
+//! [`Renderer::set_light`](struct.Renderer.html#method.set_light), which
+//! makes the terrain look lit from one side. Only the parts of a tile covered
+//! by its faces are drawn, so the faces should cover the whole cube between
+//! them. Tiles without faces are drawn whole, without lighting.
+//!
+//! ```TOML
+//! [[files.tiles]]
+//! kind = "Rock"
+//!
+//!     [files.tiles.faces]
+//!     top = { x = 0, y = 0, width = 24, height = 12 }
+//!     left = { x = 0, y = 12, width = 12, height = 12 }
+//!     right = { x = 12, y = 12, width = 12, height = 12 }
+//! ```
+//!
+//! ## Translucent blocks
+//! Blocks listed in the optional top level `translucent` key can be seen
+//! through, so their tiles should be partially transparent. If the key is
+//! missing, only `Water` is translucent, and `translucent = []` makes every
+//! block opaque.
+//!
+//! ```TOML
+//! translucent = ["Water"]
+//! ```
+//!
+//! Translucent blocks don't hide the blocks behind them, so those blocks have
+//! to be drawn even when they would otherwise be culled. Large bodies of water
+//! can therefore make rendering noticeably slower, since everything under them
+//! is drawn as well.
+//!
+//! ## Flipped tiles
+//! Tiles of the blocks listed in the optional top level `allow_flip` key are
+//! sometimes drawn flipped horizontally, which breaks up repeating patterns
+//! without any extra tiles. Every tile of those blocks gets a mirrored copy
+//! with the same weight, so a block is drawn flipped about half the time, and
+//! the copies are picked like any other variant, following the seed and the
+//! [`VariantMode`](enum.VariantMode.html). They also count towards
+//! [`tile_counts`](struct.Renderer.html#method.tile_counts).
+//!
+//! ```TOML
+//! allow_flip = ["Rock", "Soil", "Sand"]
 //! ```
 //!
+//! Only blocks whose tiles look right both ways around are safe to flip. Plain
+//! cubes of rock, soil, sand or snow usually are, since the cube itself is
+//! symmetrical. Tiles with light and shadow drawn into them aren't, because
+//! flipping moves the light to the other side, and neither are tiles with
+//! details that have to face a certain way. Tiles with
+//! [faces](#lighting) are lit after flipping, so the lighting stays right, and
+//! autotiled tiles are used for the mirrored edges once flipped.
+//!
+//! ## Animation
+//! [`Renderer::render_map_frame`](struct.Renderer.html#method.render_map_frame)
+//! renders a specific frame of animated tiles. Tiles without frames look the
+//! same in every frame. The variant of each block is picked independently of
+//! the frame, so to keep blocks from switching variants between frames, the
+//! renderer should have a seed set with
+//! [`with_seed`](struct.Renderer.html#method.with_seed).
+//!
+//! For animations where the map itself changes from frame to frame, such as
+//! slices of a map being generated, a seed isn't enough, since changing one
+//! block changes the variants of the blocks drawn after it.
+//! [`Renderer::render_frames`](struct.Renderer.html#method.render_frames)
+//! combined with
+//! [`VariantMode::PositionHashed`](enum.VariantMode.html#variant.PositionHashed)
+//! keeps every block which doesn't change on the same variant in every frame.
+//!
+//! [`Renderer::render_map_at_time`](struct.Renderer.html#method.render_map_at_time)
+//! renders the map at a point in time instead of a frame, with every block a
+//! little ahead or behind its neighbours depending on where it is in the map.
+//! Sweeping the time in small steps makes animations such as rippling water
+//! move across the map, rather than every block changing frame together.
+//!
 
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::fs;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::str::FromStr;
 
 use enum_iterator::IntoEnumIterator;
-use ndarray::Axis;
-use rand::Rng;
-use sdl2::image::LoadSurface;
-use sdl2::pixels::{PixelFormatEnum, Color};
+use ndarray::{Array3, ArrayView3, Axis};
+use rand::rngs::{SmallRng, StdRng};
+use rand::{Rng, SeedableRng};
+use sdl2::image::{ImageRWops, LoadSurface, SaveSurface};
+use sdl2::pixels::PixelFormatEnum;
+pub use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
+use sdl2::render::BlendMode;
 pub use sdl2::surface::Surface;
 pub use sdl2::rwops::RWops;
 use toml;
 
 use map::{Block, IsoMap};
 
+mod animation;
+mod builder;
+mod context;
 mod errors;
+pub use renderer::animation::encode_gif;
+pub use renderer::builder::RendererBuilder;
+pub use renderer::context::RenderContext;
 pub use renderer::errors::*;
 
+#[cfg(feature = "rayon")]
+mod parallel;
+mod axes;
+mod bands;
+mod flat;
+mod flip;
+mod legend;
+mod solid;
+
+/// Get the blocks which are translucent if the config doesn't say otherwise,
+/// which are the visible blocks that aren't opaque
+fn default_translucent() -> Vec<Block> {
+    Block::into_enum_iter()
+        .filter(|block| block != &Block::Air && !block.is_opaque())
+        .collect()
+}
+
 macro_rules! DEFAULT_BACKGROUND_COLOR{
     () => ( Color::RGB(154, 216, 224) )
 
 }
 
+/// Color of the outlines drawn around tiles by `render_debug`
+macro_rules! DEBUG_OUTLINE_COLOR {
+    () => {
+        Color::RGB(255, 0, 255)
+    };
+}
+
+/// Largest output surface we try to allocate, in bytes
+///
+/// SDL keeps the size of a surface's pixel data in an `int`, so anything
+/// larger than this fails to allocate, or worse, overflows.
+const MAX_OUTPUT_BYTES: u64 = i32::MAX as u64;
+
+/// Something which loads the spritesheet with the given filename
+type SheetLoader<'l> = dyn Fn(&str) -> Result<Surface<'static>, ConfigLoadError> + 'l;
+
+/// Load the spritesheet `filename` from the directory `base_dir`
+fn load_sheet_file(base_dir: &Path, filename: &str) -> Result<Surface<'static>, ConfigLoadError> {
+    let mut filepath = PathBuf::new();
+    filepath.push(base_dir);
+    filepath.push(filename);
+
+    Surface::from_file(filepath)
+        .map_err(|e| ConfigLoadErrorKind::from_sdl_file_err(filename, e).into())
+}
+
 /// Deserialized tiles.toml
 #[derive(Deserialize)]
 struct TilesConfig {
     width: u32,
     height: u32,
     files: Vec<File>,
+    #[serde(default)]
     base_path: String,
+    translucent: Option<Vec<Block>>,
+    #[serde(default)]
+    allow_flip: Vec<Block>,
+    color_key: Option<[u8; 3]>,
 }
 
 #[derive(Deserialize)]
 struct File {
     filename: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    color_key: Option<[u8; 3]>,
+    #[serde(default)]
     tiles: Vec<TileDef>,
+    #[serde(default)]
+    grid: Vec<GridDef>,
 }
 
 #[derive(Deserialize)]
@@ -81,6 +322,102 @@ struct TileDef {
     kind: Block,
     x: Option<i32>,
     y: Option<i32>,
+    weight: Option<u32>,
+    frames: Option<u32>,
+    stride: Option<i32>,
+    autotile: Option<Vec<Edge>>,
+    faces: Option<FacesDef>,
+    top_offset: Option<u32>,
+}
+
+/// The visible faces of a cube, as parts of its tile, so that they can be lit
+/// separately
+#[derive(Deserialize)]
+struct FacesDef {
+    top: FaceDef,
+    left: FaceDef,
+    right: FaceDef,
+}
+
+/// A rectangle within a tile, relative to the tile's upper left corner
+#[derive(Deserialize)]
+struct FaceDef {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl FacesDef {
+    /// Get the rectangles of the top, left and right faces
+    fn rects(&self) -> [Rect; 3] {
+        let rect = |face: &FaceDef| Rect::new(face.x, face.y, face.width, face.height);
+
+        [rect(&self.top), rect(&self.left), rect(&self.right)]
+    }
+}
+
+/// A side of a block which borders on a drop, used to pick autotiled variants
+///
+/// Directions are as seen on screen: north is up and to the right, along the
+/// negative y axis, and the rest follow clockwise.
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+enum Edge {
+    #[serde(rename = "edge_n")]
+    North,
+    #[serde(rename = "edge_e")]
+    East,
+    #[serde(rename = "edge_s")]
+    South,
+    #[serde(rename = "edge_w")]
+    West,
+}
+
+impl Edge {
+    /// Get the bit representing this edge in an edge mask
+    fn bit(self) -> u8 {
+        match self {
+            Edge::North => 1,
+            Edge::East => 2,
+            Edge::South => 4,
+            Edge::West => 8,
+        }
+    }
+}
+
+/// A number of variants of one block, laid out in a grid in the sheet
+#[derive(Deserialize)]
+struct GridDef {
+    kind: Block,
+    x: Option<i32>,
+    y: Option<i32>,
+    count: u32,
+    columns: Option<u32>,
+    stride_x: Option<i32>,
+    stride_y: Option<i32>,
+    weight: Option<u32>,
+}
+
+impl GridDef {
+    /// Expand the grid into a definition for every tile in it
+    fn expand(&self, tile_width: u32, tile_height: u32) -> Vec<TileDef> {
+        let columns = self.columns.unwrap_or(self.count).max(1);
+        let stride_x = self.stride_x.unwrap_or(tile_width as i32);
+        let stride_y = self.stride_y.unwrap_or(tile_height as i32);
+
+        (0..self.count)
+            .map(|i| TileDef {
+                kind: self.kind,
+                x: Some(self.x.unwrap_or(0) + (i % columns) as i32 * stride_x),
+                y: Some(self.y.unwrap_or(0) + (i / columns) as i32 * stride_y),
+                weight: self.weight,
+                frames: None,
+                stride: None,
+                autotile: None,
+                faces: None,
+                top_offset: None,
+            }).collect()
+    }
 }
 
 /// A single tile, to be used in rendering the map
@@ -92,6 +429,184 @@ struct TileDef {
 struct Tile<'a> {
     sheet: Rc<Surface<'a>>,
     pos: Rect,
+
+    /// Relative likelihood of this tile being picked among the tiles for its
+    /// block
+    weight: u32,
+
+    /// Number of animation frames, which follow `pos` in the sheet
+    frames: u32,
+
+    /// Horizontal distance between animation frames
+    stride: i32,
+
+    /// Mask of the edges this tile is meant for, or `None` for a plain tile
+    /// which can be used anywhere
+    autotile: Option<u8>,
+
+    /// The top, left and right faces of the cube, relative to `pos`, or `None`
+    /// if the tile is drawn whole
+    faces: Option<[Rect; 3]>,
+
+    /// Distance from the top of the tile down to the top of the cube's top
+    /// face, which is drawn at the block's position
+    top_offset: u32,
+}
+
+impl<'a> Tile<'a> {
+    /// Get the position of `frame` of this tile in its sheet
+    ///
+    /// Frames loop, so any frame index is valid. A tile without animation has
+    /// only one frame.
+    fn frame_pos(&self, frame: u32) -> Rect {
+        let mut pos = self.pos;
+        pos.offset((frame % self.frames) as i32 * self.stride, 0);
+        pos
+    }
+
+    /// Get the parts to copy out of the sheet to draw `frame` of this tile at
+    /// `dest`, and the brightness to draw each of them at
+    ///
+    /// A tile without faces is a single part at `brightness`. Otherwise, every
+    /// face is a part of its own, darkened further by its multiplier in
+    /// `light`.
+    fn parts(
+        &self,
+        frame: u32,
+        dest: Rect,
+        brightness: u8,
+        light: [f32; 3],
+    ) -> Vec<(Rect, Rect, u8)> {
+        let src = self.frame_pos(frame);
+        let faces = match self.faces {
+            Some(faces) => faces,
+            None => return vec![(src, dest, brightness)],
+        };
+
+        // Faces are scaled up along with the rest of the tile
+        let scale = (dest.width() / self.pos.width().max(1)).max(1);
+
+        faces
+            .iter()
+            .zip(&light)
+            .map(|(face, &light)| {
+                let face_src = Rect::new(
+                    src.x() + face.x(),
+                    src.y() + face.y(),
+                    face.width(),
+                    face.height(),
+                );
+                let face_dest = Rect::new(
+                    dest.x() + face.x() * scale as i32,
+                    dest.y() + face.y() * scale as i32,
+                    face.width() * scale,
+                    face.height() * scale,
+                );
+
+                (face_src, face_dest, (f32::from(brightness) * light).round() as u8)
+            }).collect()
+    }
+}
+
+/// The direction a map is viewed from
+///
+/// Each direction turns the view by 90° from the previous one, so rendering a
+/// map from all four in order makes a turntable around it.
+#[derive(Copy, IntoEnumIterator, Clone, PartialEq, Eq, Debug)]
+pub enum Facing {
+    /// The default view, with the x axis going down and to the right, and the
+    /// y axis going down and to the left
+    North,
+    East,
+    South,
+    West,
+}
+
+/// What the renderer does with blocks in a map which it has no tiles for
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MissingPolicy {
+    /// Fail the render with
+    /// [`RendererError::MissingTile`](enum.RendererError.html#variant.MissingTile),
+    /// the default
+    Error,
+
+    /// Leave the blocks out, as though they were air
+    Skip,
+
+    /// Draw the tiles of another block in their place. If there are no tiles
+    /// for that block either, the render fails as with `Error`.
+    Fallback(Block),
+}
+
+/// How the renderer picks tile variants for blocks
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VariantMode {
+    /// Pick variants one after another from a single stream of random
+    /// numbers, the default. Adding or removing a block changes the variants
+    /// of every block drawn after it.
+    RandomStream,
+
+    /// Pick the variant of each block based on its position in the map, so
+    /// that it stays the same no matter what else is in the map, or which way
+    /// it's viewed from
+    PositionHashed,
+}
+
+/// The width and height of an output surface, along with the tiles to draw on
+/// it, their destinations, the brightness to draw them at, the floor they're
+/// on and the phase of their animation, in the order they need to be drawn
+type Layout<'t, 'a> = (u32, u32, Vec<(&'t Tile<'a>, Rect, u8, usize, f64)>);
+
+/// How far along their animations to draw animated tiles
+#[derive(Clone, Copy)]
+enum Clock {
+    /// The same frame for every tile
+    Frame(u32),
+
+    /// A point in time, which every tile is ahead of by its phase
+    Time(f64),
+}
+
+impl Clock {
+    /// Get the frame to draw a tile with the animation phase `phase` at
+    fn frame(self, phase: f64) -> u32 {
+        match self {
+            Clock::Frame(frame) => frame,
+            Clock::Time(time) => (time + phase).max(0.0) as u32,
+        }
+    }
+}
+
+/// Reports progress through the floors of a map to a callback, once for every
+/// floor
+struct FloorProgress<'p> {
+    floors: usize,
+    done: usize,
+    callback: &'p mut dyn FnMut(f32),
+}
+
+impl<'p> FloorProgress<'p> {
+    fn new(floors: usize, callback: &'p mut dyn FnMut(f32)) -> FloorProgress<'p> {
+        FloorProgress {
+            floors,
+            done: 0,
+            callback,
+        }
+    }
+
+    /// Report every floor below `z` as done, if it hasn't been already
+    fn reach(&mut self, z: usize) {
+        while self.done < z.min(self.floors) {
+            self.done += 1;
+            (self.callback)(self.done as f32 / self.floors as f32);
+        }
+    }
+
+    /// Report every remaining floor as done
+    fn finish(&mut self) {
+        let floors = self.floors;
+        self.reach(floors);
+    }
 }
 
 /// Config used by the renderer to pick tiles
@@ -107,51 +622,267 @@ pub struct Renderer<'a> {
     /// Each block type can have multiple tiles. The tile to use is picked at
     /// random every time a block is rendered.
     tiles: HashMap<Block, Vec<Tile<'a>>>,
+
+    /// Color to fill the background of renders with
+    background: Color,
+
+    /// Seed for picking tile variants. If unset, every render picks variants
+    /// anew.
+    seed: Option<u64>,
+
+    /// Whether to skip blocks which are completely hidden by other blocks
+    cull: bool,
+
+    /// Blocks which can be seen through, and so don't hide what's behind them
+    translucent: Vec<Block>,
+
+    /// Direction the map is viewed from
+    facing: Facing,
+
+    /// Factor to scale renders up by
+    scale: u32,
+
+    /// How much to darken the lowest floor of the map, between 0 and 1
+    depth_shading: f32,
+
+    /// Space to leave around the map, in tiles, split evenly between opposite
+    /// sides
+    margin: u32,
+
+    /// Whether to draw a legend of the blocks in the map
+    legend: bool,
+
+    /// Brightness of the top, left and right faces of tiles which have them,
+    /// between 0 and 1
+    light: [f32; 3],
+
+    /// What to do with blocks which have no tiles
+    missing: MissingPolicy,
+
+    /// How tile variants are picked
+    variants: VariantMode,
+
+    /// How much to darken blocks in corners, between 0 and 1
+    ao: f32,
+
+    /// Whether to draw the directions of the map's axes
+    axes: bool,
+
+    /// How many steps from the corner nearest the viewer blocks are hidden
+    /// within
+    cutaway: usize,
 }
 
 impl<'a> Renderer<'a> {
+    /// Default space around the map, in tiles
+    const DEFAULT_MARGIN: u32 = 2;
+
     /// Create new RendererConfig from a TOML configuration provided in `input`
     ///
     /// `s` is the string with the config TOML.
     pub fn from_config_str(s: &str) -> Result<Self, ConfigLoadError> {
         use failure::ResultExt;
 
-        let parsed: TilesConfig = toml::from_str(s).context(ConfigLoadErrorKind::TomlParseError)?;
+        let parsed: TilesConfig =
+            toml::from_str(s).context(ConfigLoadErrorKind::TomlParseError { filename: None })?;
+        let base_dir = PathBuf::from(&parsed.base_path);
+
+        Self::from_tiles_config(parsed, &|filename| load_sheet_file(&base_dir, filename))
+    }
+
+    /// Create new RendererConfig from a TOML configuration provided in `s`,
+    /// looking for spritesheets in `base` instead of the config's `base_path`
+    ///
+    /// This lets one config be used with tiles from different directories.
+    pub fn from_config_str_with_base(s: &str, base: &Path) -> Result<Self, ConfigLoadError> {
+        use failure::ResultExt;
+
+        let parsed: TilesConfig =
+            toml::from_str(s).context(ConfigLoadErrorKind::TomlParseError { filename: None })?;
+
+        Self::from_tiles_config(parsed, &|filename| load_sheet_file(base, filename))
+    }
+
+    /// Create new RendererConfig from a TOML configuration provided in `s`,
+    /// with the spritesheets given as the contents of their files in `sheets`
+    ///
+    /// `sheets` maps the filenames in the config to the bytes of the images,
+    /// in any format SDL_image can load, so nothing is read from the
+    /// filesystem. This is useful when the spritesheets are included in the
+    /// binary. A file missing from `sheets` is an error.
+    pub fn from_config_str_with_sheets(
+        s: &str,
+        sheets: &HashMap<String, &[u8]>,
+    ) -> Result<Self, ConfigLoadError> {
+        use failure::ResultExt;
+
+        let parsed: TilesConfig =
+            toml::from_str(s).context(ConfigLoadErrorKind::TomlParseError { filename: None })?;
+
+        Self::from_tiles_config(parsed, &|filename| {
+            let bytes = sheets
+                .get(filename)
+                .ok_or_else(|| ConfigLoadErrorKind::MissingSheet(filename.to_string()))?;
+
+            RWops::from_bytes(bytes)
+                .and_then(|rwops| rwops.load())
+                .map_err(|e| ConfigLoadErrorKind::from_sdl_file_err(filename, e).into())
+        })
+    }
+
+    /// Create new RendererConfig from a TOML configuration file at `path`
+    ///
+    /// If `base_path` in the config is relative, it is resolved relative to
+    /// the directory containing the config file, rather than the current
+    /// working directory.
+    pub fn from_config_file(path: &Path) -> Result<Self, ConfigLoadError> {
+        use failure::ResultExt;
+
+        let s = fs::read_to_string(path).context(ConfigLoadErrorKind::FileReadError)?;
+        let parsed: TilesConfig = toml::from_str(&s).context(ConfigLoadErrorKind::TomlParseError {
+            filename: Some(path.display().to_string()),
+        })?;
+
+        let mut base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        base_dir.push(&parsed.base_path);
+
+        Self::from_tiles_config(parsed, &|filename| load_sheet_file(&base_dir, filename))
+    }
+
+    /// Create new RendererConfig from a TOML configuration read from `r`
+    ///
+    /// If `base_path` in the config is relative, it is resolved relative to
+    /// `base`, the same way as it is relative to the directory containing the
+    /// config file with [`from_config_file`](#method.from_config_file).
+    /// Failing to read `r`, or reading something that isn't UTF-8, is a
+    /// [`FileReadError`](enum.ConfigLoadErrorKind.html#variant.FileReadError).
+    pub fn from_config_reader(mut r: impl Read, base: &Path) -> Result<Self, ConfigLoadError> {
+        use failure::ResultExt;
+
+        let mut s = String::new();
+        r.read_to_string(&mut s).context(ConfigLoadErrorKind::FileReadError)?;
+        let parsed: TilesConfig =
+            toml::from_str(&s).context(ConfigLoadErrorKind::TomlParseError { filename: None })?;
+
+        let base_dir = base.join(&parsed.base_path);
+
+        Self::from_tiles_config(parsed, &|filename| load_sheet_file(&base_dir, filename))
+    }
+
+    /// Load the tiles described by an already parsed config, getting the
+    /// spritesheet for every file from `load_sheet`
+    fn from_tiles_config(
+        parsed: TilesConfig,
+        load_sheet: &SheetLoader,
+    ) -> Result<Self, ConfigLoadError> {
         let tile_width = parsed.width;
         let tile_height = parsed.height;
-        let base_dir = parsed.base_path;
+        let translucent = parsed
+            .translucent
+            .unwrap_or_else(default_translucent);
+        let color_key = parsed.color_key;
+        let allow_flip = parsed.allow_flip;
 
         let files_with_tiles: Vec<Vec<(Block, Tile)>> = parsed
             .files
             .into_iter()
             .map(|file| -> Result<Vec<(Block, Tile)>, _> {
-                let mut filepath = PathBuf::new();
-                filepath.push(&base_dir);
-                filepath.push(&file.filename);
+                let File {
+                    filename,
+                    width,
+                    height,
+                    color_key: file_color_key,
+                    mut tiles,
+                    grid,
+                } = file;
+                let tile_width = width.unwrap_or(tile_width);
+                let tile_height = height.unwrap_or(tile_height);
+
+                for grid in &grid {
+                    tiles.extend(grid.expand(tile_width, tile_height));
+                }
 
                 // load each file to a surface...
-                let surf = Rc::new(
-                    Surface::from_file(filepath)
-                        .map_err(ConfigLoadErrorKind::from_sdl_string_err)?,
-                );
+                let mut surf = load_sheet(&filename)?;
+
+                // Tiles are always alpha blended over what's behind them, so
+                // that transparent pixels around the edges of a tile show the
+                // background or the tiles below. Sheets with an alpha channel
+                // usually blend already, but not every image loader sets it.
+                surf.set_blend_mode(BlendMode::Blend)
+                    .map_err(|e| ConfigLoadErrorKind::from_sdl_file_err(&filename, e))?;
+
+                if let Some([r, g, b]) = file_color_key.or(color_key) {
+                    surf.set_color_key(true, Color::RGB(r, g, b))
+                        .map_err(|e| ConfigLoadErrorKind::from_sdl_file_err(&filename, e))?;
+                }
+
+                let surf = Rc::new(surf);
 
                 // ...and then refer to that surface in Tile instances, along
                 // with the offsets
-                Ok(file
-                    .tiles
+                tiles
                     .into_iter()
-                    .map(|tiledef| -> (Block, Tile) {
+                    .map(|tiledef| -> Result<(Block, Tile), ConfigLoadError> {
                         let x = tiledef.x.unwrap_or(0);
                         let y = tiledef.y.unwrap_or(0);
 
-                        (
-                            tiledef.kind,
-                            Tile {
-                                sheet: Rc::clone(&surf),
-                                pos: Rect::new(x, y, tile_width, tile_height),
-                            },
-                        )
-                    }).collect::<Vec<(Block, Tile)>>())
+                        let tile = Tile {
+                            sheet: Rc::clone(&surf),
+                            pos: Rect::new(x, y, tile_width, tile_height),
+                            weight: tiledef.weight.unwrap_or(1),
+                            frames: tiledef.frames.unwrap_or(1).max(1),
+                            stride: tiledef.stride.unwrap_or(tile_width as i32),
+                            autotile: tiledef
+                                .autotile
+                                .map(|edges| edges.iter().fold(0, |mask, e| mask | e.bit())),
+                            faces: tiledef.faces.as_ref().map(FacesDef::rects),
+                            top_offset: tiledef.top_offset.unwrap_or(0),
+                        };
+
+                        // The top face has to start within the tile
+                        if tile.top_offset >= tile_height {
+                            return Err(ConfigLoadError::from(
+                                ConfigLoadErrorKind::TopOffsetOutOfBounds(tiledef.kind),
+                            ));
+                        }
+
+                        let tile_bounds = Rect::new(0, 0, tile_width, tile_height);
+                        let faces_fit = match tile.faces {
+                            Some(faces) => {
+                                faces.iter().all(|face| tile_bounds.contains_rect(*face))
+                            }
+                            None => true,
+                        };
+
+                        if !faces_fit {
+                            return Err(ConfigLoadError::from(
+                                ConfigLoadErrorKind::FaceOutOfBounds(tiledef.kind),
+                            ));
+                        }
+
+                        // Every frame has to fit, but checking the first and
+                        // the last covers the ones in between
+                        let sheet_bounds = surf.rect();
+                        let in_bounds = |pos: Rect| {
+                            pos.x() >= 0
+                                && pos.y() >= 0
+                                && pos.right() <= sheet_bounds.right()
+                                && pos.bottom() <= sheet_bounds.bottom()
+                        };
+
+                        let last_frame = tile.frame_pos(tile.frames - 1);
+                        if !in_bounds(tile.frame_pos(0)) || !in_bounds(last_frame) {
+                            return Err(ConfigLoadError::from(
+                                ConfigLoadErrorKind::TileOutOfBounds {
+                                    filename: filename.clone(),
+                                    block: tiledef.kind,
+                                },
+                            ));
+                        }
+
+                        Ok((tiledef.kind, tile))
+                    }).collect::<Result<Vec<(Block, Tile)>, ConfigLoadError>>()
             }).collect::<Result<Vec<Vec<(Block, Tile)>>, ConfigLoadError>>()?;
 
         let mut tiles_map: HashMap<Block, Vec<Tile>> = HashMap::new();
@@ -164,13 +895,45 @@ impl<'a> Renderer<'a> {
                 .push(tile)
         }
 
-        // Ensure each block has at least one tile
+        flip::add_flipped_tiles(&mut tiles_map, &allow_flip)?;
+
+        let renderer = Self::from_tiles(tile_width, tile_height, tiles_map)?;
+
+        Ok(Renderer {
+            translucent,
+            ..renderer
+        })
+    }
+
+    /// Create a renderer out of already loaded tiles, checking that every
+    /// block has at least one, and that the tile width works with the
+    /// projection
+    // u32::is_multiple_of is far newer than anything else the crate needs
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    fn from_tiles(
+        width: u32,
+        height: u32,
+        tiles: HashMap<Block, Vec<Tile<'a>>>,
+    ) -> Result<Self, ConfigLoadError> {
+        // Tiles are placed a quarter of the width apart vertically, so any
+        // other width leaves gaps that grow across the map
+        if width == 0 || width % 4 != 0 {
+            return Err(ConfigLoadError::from(ConfigLoadErrorKind::BadTileWidth(width)));
+        }
+
+        // Ensure each block has at least one tile. An empty list of tiles is
+        // as good as none, since there's nothing to pick from.
         for block in Block::into_enum_iter() {
             if block == Block::Air {
                 continue; // We special-case air since it doesn't need tiles
             }
 
-            if !tiles_map.contains_key(&block) {
+            let has_tiles = match tiles.get(&block) {
+                Some(block_tiles) => !block_tiles.is_empty(),
+                None => false,
+            };
+
+            if !has_tiles {
                 return Err(ConfigLoadError::from(ConfigLoadErrorKind::MissingBlock(
                     block,
                 )));
@@ -178,117 +941,2726 @@ impl<'a> Renderer<'a> {
         }
 
         Ok(Renderer {
-            width: tile_width,
-            height: tile_height,
-            tiles: tiles_map,
+            width,
+            height,
+            tiles,
+            background: DEFAULT_BACKGROUND_COLOR!(),
+            seed: None,
+            cull: true,
+            translucent: default_translucent(),
+            facing: Facing::North,
+            scale: 1,
+            depth_shading: 0.0,
+            margin: Self::DEFAULT_MARGIN,
+            legend: false,
+            light: [1.0; 3],
+            missing: MissingPolicy::Error,
+            variants: VariantMode::RandomStream,
+            ao: 0.0,
+            axes: false,
+            cutaway: 0,
         })
     }
 
-    /// Render an `IsoMap` using a `Renderer`
-    pub fn render_map<'b>(&self, isomap: &IsoMap) -> Result<Surface<'b>, RendererError> {
-        
-        // Pixel height of the top face of the cube. Since we're in a 2:1
-        // projection, it's half the tile's width. 
-        let top_height: u32 = self.width/2;
-
-        // Pixel height of the sides of the cube. This is the remainder of the
-        // pixel height of a tile, after we account for the top face.
-        let sides_height: u32 = self.height - top_height;
+    /// Get the number of tile variants loaded for every block
+    ///
+    /// Blocks without any tiles, such as air, have no entry.
+    pub fn tile_counts(&self) -> HashMap<Block, usize> {
+        self.tiles
+            .iter()
+            .map(|(block, tiles)| (*block, tiles.len()))
+            .collect()
+    }
 
-        // How much a single floor takes up in pixels, in the vertical. The longest part vertically
-        // is the diagnoal. If we walk up or down the diagonal, we'll move by one full top_height
-        // for each tile. Then, we'll also be able to see the frontmost tile's sides, so we add
-        // sides_height.
-        let floor_height: u32 = (isomap.len() as u32 * top_height) + sides_height; 
+    /// Get the width and height of a single tile, in pixels
+    ///
+    /// This is the size of the tiles in the spritesheets, regardless of any
+    /// [scaling](#method.set_scale). Files which set a tile size of their own
+    /// can have tiles of a different size.
+    pub fn tile_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
 
-        // We make the surface wide enough to take the width of a floor and then
-        // add a margin
-        let surf_width: u32 = (self.width * isomap.len() as u32) + (self.width * 2);
+    /// Set the background color of renders
+    ///
+    /// If the color is not fully opaque, renders will have an alpha channel,
+    /// so that the background can be transparent.
+    pub fn set_background(self, color: Color) -> Self {
+        Renderer {
+            background: color,
+            ..self
+        }
+    }
 
-        // We need enough room for a single floor, then every floor stack on top
-        // of it, then some margins
-        let surf_height: u32 = floor_height + (sides_height * isomap.len() as u32) + (self.height * 2);
+    /// Set a seed for picking tile variants
+    ///
+    /// With a seed set, every render of the same `IsoMap` will be identical.
+    /// Without one, tile variants are picked at random on each render.
+    pub fn with_seed(self, seed: u64) -> Self {
+        Renderer {
+            seed: Some(seed),
+            ..self
+        }
+    }
 
-        let mut out = Surface::new(surf_width, surf_height, PixelFormatEnum::RGB24)?;
-        out.fill_rect(None, DEFAULT_BACKGROUND_COLOR!())?;
+    /// Set how tile variants are picked for blocks
+    ///
+    /// By default, variants are picked with
+    /// [`VariantMode::RandomStream`](enum.VariantMode.html), so changing one
+    /// block of a map reshuffles the variants of many others. With
+    /// `VariantMode::PositionHashed`, a block's variant only depends on its
+    /// position and on the seed set with [`with_seed`](#method.with_seed).
+    pub fn set_variant_mode(self, variants: VariantMode) -> Self {
+        Renderer { variants, ..self }
+    }
 
-        // In the x axis, we find the midpoint, and then shift a bit to the
-        // left, so that half of the tile is to the left of the midpoint, and
-        // the other half to the right.
-        //
-        // In the y axis, we start from the bottom, go up to account for the
-        // margin, and then go up to account for the floor height.
-        let mut current_origin = Point::new(
-            (surf_width / 2 - self.width / 2) as i32,
-            surf_height as i32 - self.height as i32 - floor_height as i32, 
-        );
+    /// Set whether to skip drawing blocks which are completely hidden
+    ///
+    /// A block is hidden when the blocks in front of it in both horizontal
+    /// directions and the block above it are all present. Culling is on by
+    /// default and doesn't change the output; turning it off is only useful
+    /// for diagnostics.
+    pub fn set_cull(self, cull: bool) -> Self {
+        Renderer { cull, ..self }
+    }
 
-        for floor in isomap.0.axis_iter(Axis(2)) {
-            for ((x, y), tile) in floor.indexed_iter() {
-                if tile == &Block::Air {
-                    continue; // blank, do nothing
-                }
+    /// Set what to do with blocks in a map which there are no tiles for
+    ///
+    /// Every named block has tiles once a renderer is loaded, so this only
+    /// matters for custom blocks. By default, such a map fails to render with
+    /// [`MissingPolicy::Error`](enum.MissingPolicy.html). Skipped blocks don't
+    /// hide anything behind them, just like air.
+    pub fn set_missing_policy(self, missing: MissingPolicy) -> Self {
+        Renderer { missing, ..self }
+    }
 
-                let tile_dest = self.get_tile_pos(current_origin, x, y);
-                let tile_sprite = self.get_random_sprite(tile);
+    /// Set the direction the map is viewed from
+    ///
+    /// By default, maps are viewed from [`Facing::North`](enum.Facing.html).
+    /// The tiles are the same from every direction, only the map is turned.
+    pub fn set_facing(self, facing: Facing) -> Self {
+        Renderer { facing, ..self }
+    }
 
-                tile_sprite.sheet.clone().blit(
-                    tile_sprite.pos,
-                    &mut out,
-                    Rect::new(tile_dest.x, tile_dest.y, self.width, self.height),
-                )?;
-            }
+    /// Set how much to darken lower floors of the map
+    ///
+    /// The top floor is always drawn as is, and every floor below it is
+    /// darker, down to the bottom floor, which is darkened by `factor`. At 0,
+    /// the default, nothing is darkened, and at 1, the bottom floor is
+    /// completely black. Shading copies every spritesheet on each render, so
+    /// it's slightly slower.
+    pub fn set_depth_shading(self, factor: f32) -> Self {
+        Renderer {
+            depth_shading: factor.clamp(0.0, 1.0),
+            ..self
+        }
+    }
 
-            // Shift to the floor above
-            current_origin = current_origin.offset(0, -(sides_height as i32));
+    /// Set how much to darken blocks which sit in corners
+    ///
+    /// This is a simple kind of ambient occlusion. Blocks are darkened for
+    /// every solid block one floor above them which is right behind them on
+    /// screen, that is, up and to the left, up and to the right, or straight
+    /// up. With all three, a block is darkened by `strength`, which is clamped
+    /// between 0 and 1. At 0, the default, nothing is darkened. This combines
+    /// with depth shading, and copies the spritesheets on each render like it.
+    pub fn set_ao(self, strength: f32) -> Self {
+        Renderer {
+            ao: strength.clamp(0.0, 1.0),
+            ..self
         }
+    }
 
-        Ok(out)
+    /// Set the brightness of the top, left and right faces of cubes
+    ///
+    /// This only affects tiles which have their faces set in the config, as
+    /// described in [the module documentation](index.html#lighting). Each
+    /// multiplier is clamped between 0 and 1, with 1, the default, drawing the
+    /// face as it is in the sheet. For example, `set_light(1.0, 0.8, 0.6)`
+    /// looks like sunlight coming from the upper left. Like depth shading,
+    /// lighting copies the spritesheets on each render.
+    pub fn set_light(self, top: f32, left: f32, right: f32) -> Self {
+        Renderer {
+            light: [
+                top.clamp(0.0, 1.0),
+                left.clamp(0.0, 1.0),
+                right.clamp(0.0, 1.0),
+            ],
+            ..self
+        }
     }
 
-    /// Get pixel position for a tile at map position `x_index`, `y_index`, assuming tile 0,0 is at `origin`.
-    fn get_tile_pos(&self, origin: Point, x_index: usize, y_index: usize) -> Point {
-        // Tile tops (the top surfaces of the cube) are assumed to be at a 2:1
-        // ratio, twice as wide as they are tall.
-        origin.offset(
-            (x_index as i32 - y_index as i32) * (self.width as i32 / 2),
-            (x_index as i32 + y_index as i32) * (self.width as i32 / 4),
-        )
+    /// Set an integer factor to scale renders up by
+    ///
+    /// Tiles are scaled with nearest neighbour filtering, so every pixel of a
+    /// tile becomes a `factor` by `factor` square. The default is 1, which
+    /// renders tiles at their original size. A factor of 0 is treated as 1.
+    pub fn set_scale(self, factor: u32) -> Self {
+        Renderer {
+            scale: factor.max(1),
+            ..self
+        }
     }
 
-    fn get_random_sprite(&self, tile_type: &Block) -> &Tile {
-        // We unwrap here because from_str should never leave us in a state
-        // where some tiles are missing
-        let potential_tiles = self
-            .tiles
-            .get(&tile_type)
-            .expect("renderer config missing tiles for a block type");
+    /// Set the space to leave around the map, in tiles
+    ///
+    /// The margin is split evenly between opposite sides, so the default of 2
+    /// leaves the width of one tile to the left and right of the map, and the
+    /// height of one tile above and below it. A margin of 0 makes the output
+    /// as small as possible.
+    pub fn set_margin(self, tiles: u32) -> Self {
+        Renderer {
+            margin: tiles,
+            ..self
+        }
+    }
 
-        rand::thread_rng()
-            .choose(potential_tiles)
-            .expect("renderer config has an empty vector for a block type")
+    /// Set whether to draw a legend in the upper left corner of renders
+    ///
+    /// The legend has a row for every kind of block in the map, with one of
+    /// its tiles and its name. It's drawn over the margin, and over the map if
+    /// there isn't enough room, so it's meant for diagnostics rather than
+    /// finished images. There's no legend by default.
+    pub fn set_legend(self, legend: bool) -> Self {
+        Renderer { legend, ..self }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::env;
+    /// Set whether to draw the directions of the map's axes over renders
+    ///
+    /// Three lines, two blocks long, start from the bottom corner of block
+    /// 0,0,0: a red one along the x axis, a green one along the y axis and a
+    /// blue one along the z axis, each labelled with its axis at the end.
+    /// They follow the map as it's turned with
+    /// [`set_facing`](#method.set_facing). Like the legend, they're meant for
+    /// diagnostics, and they're off by default.
+    pub fn set_axes(self, axes: bool) -> Self {
+        Renderer { axes, ..self }
+    }
 
-    #[test]
+    /// Hide the blocks near the corner of the map closest to the viewer
+    ///
+    /// Blocks fewer than `depth` steps along the x and y axes away from the
+    /// bottom corner of the map on screen are left out on every floor, which
+    /// cuts a notch out of the front of the map and shows what's inside it.
+    /// The corner follows the map as it's turned with
+    /// [`set_facing`](#method.set_facing). Combined with
+    /// [`render_map_layers`](#method.render_map_layers), this makes
+    /// architectural-style cutaways. The default of 0 hides nothing.
+    pub fn set_cutaway(self, depth: usize) -> Self {
+        Renderer {
+            cutaway: depth,
+            ..self
+        }
+    }
+
+    /// Render an `IsoMap` using a `Renderer`
+    pub fn render_map<'b>(&self, isomap: &IsoMap) -> Result<Surface<'b>, RendererError> {
+        self.render_map_with_progress(isomap, |_| ())
+    }
+
+    /// Render an `IsoMap`, calling `progress` as the render goes on
+    ///
+    /// `progress` is called once after every floor of the map is drawn, with
+    /// the fraction of floors done so far, from above 0 up to 1 for the last
+    /// floor. The render is otherwise the same as with
+    /// [`render_map`](#method.render_map). A map without any floors doesn't
+    /// call `progress` at all.
+    pub fn render_map_with_progress<'b, F: FnMut(f32)>(
+        &self,
+        isomap: &IsoMap,
+        mut progress: F,
+    ) -> Result<Surface<'b>, RendererError> {
+        match self.seed {
+            Some(seed) => self.render_map_with_rng(
+                isomap,
+                Clock::Frame(0),
+                &mut StdRng::seed_from_u64(seed),
+                &mut progress,
+            ),
+            None => self.render_map_with_rng(
+                isomap,
+                Clock::Frame(0),
+                &mut rand::thread_rng(),
+                &mut progress,
+            ),
+        }
+    }
+
+    /// Render an `IsoMap` into an existing surface
+    ///
+    /// `target` must be at least as large as
+    /// [`output_dimensions`](#method.output_dimensions) for the map, otherwise
+    /// [`RendererError::TargetTooSmall`](enum.RendererError.html#variant.TargetTooSmall)
+    /// is returned and nothing is drawn. The map is drawn in the upper left
+    /// corner, and the whole of `target` is cleared to the background first.
+    /// Reusing a surface avoids allocating a new one for every frame.
+    pub fn render_map_into(&self, isomap: &IsoMap, target: &mut Surface) -> Result<(), RendererError> {
+        match self.seed {
+            Some(seed) => self.render_map_into_with_rng(
+                isomap,
+                Clock::Frame(0),
+                target,
+                &mut StdRng::seed_from_u64(seed),
+                &mut |_| (),
+            ),
+            None => self.render_map_into_with_rng(
+                isomap,
+                Clock::Frame(0),
+                target,
+                &mut rand::thread_rng(),
+                &mut |_| (),
+            ),
+        }
+    }
+
+    /// Render an `IsoMap`, using frame `frame` of animated tiles
+    ///
+    /// Frames loop, so the frame index can be higher than the number of frames
+    /// a tile has. Tiles without animation look the same in every frame. See
+    /// [the module documentation](index.html#animation) for details.
+    pub fn render_map_frame<'b>(
+        &self,
+        isomap: &IsoMap,
+        frame: u32,
+    ) -> Result<Surface<'b>, RendererError> {
+        self.render_map_at_clock(isomap, Clock::Frame(frame))
+    }
+
+    /// Render an `IsoMap` at time `t` of the animations of animated tiles
+    ///
+    /// Rather than drawing every block on the same frame, as
+    /// [`render_map_frame`](#method.render_map_frame) does, each block is
+    /// ahead of `t` by a phase which depends on its position, and is drawn
+    /// with frame `t + phase`, rounded down. The phase grows by a quarter of a
+    /// frame with every step along the x or y axis of the map, so when `t` is
+    /// swept smoothly to make the frames of an animation, something like water
+    /// seems to ripple across the map instead of every block changing at once.
+    /// Tiles without animation look the same at any time. See
+    /// [the module documentation](index.html#animation) for details.
+    pub fn render_map_at_time<'b>(
+        &self,
+        isomap: &IsoMap,
+        t: f64,
+    ) -> Result<Surface<'b>, RendererError> {
+        self.render_map_at_clock(isomap, Clock::Time(t))
+    }
+
+    /// Render an `IsoMap` with animated tiles at `clock`
+    fn render_map_at_clock<'b>(
+        &self,
+        isomap: &IsoMap,
+        clock: Clock,
+    ) -> Result<Surface<'b>, RendererError> {
+        match self.seed {
+            Some(seed) => self.render_map_with_rng(
+                isomap,
+                clock,
+                &mut StdRng::seed_from_u64(seed),
+                &mut |_| (),
+            ),
+            None => self.render_map_with_rng(isomap, clock, &mut rand::thread_rng(), &mut |_| ()),
+        }
+    }
+
+    /// Render an `IsoMap` and save the result as a PNG file at `path`
+    ///
+    /// The PNG is an 8-bit RGB image with the same dimensions as the surface
+    /// returned by [`render_map`](#method.render_map). If the background is
+    /// not fully opaque, the PNG will be RGBA instead.
+    pub fn render_map_to_png(&self, isomap: &IsoMap, path: &Path) -> Result<(), RendererError> {
+        self.render_map(isomap)?.save(path)?;
+
+        Ok(())
+    }
+
+    /// Render an `IsoMap` to a buffer of raw pixels
+    ///
+    /// Returns tightly packed RGBA8 bytes, row by row from the top, along with
+    /// the width and height of the image. This is useful for handing the
+    /// render to something other than SDL.
+    pub fn render_map_to_buffer(
+        &self,
+        isomap: &IsoMap,
+    ) -> Result<(Vec<u8>, u32, u32), RendererError> {
+        let surf = self.render_map(isomap)?;
+
+        Ok((surface_to_rgba(&surf), surf.width(), surf.height()))
+    }
+
+    /// Render an `IsoMap`, picking tile variants with an RNG seeded by `seed`
+    ///
+    /// Rendering the same map with the same seed will always produce the same
+    /// image, while different seeds will pick different variants.
+    pub fn render_map_seeded<'b>(
+        &self,
+        isomap: &IsoMap,
+        seed: u64,
+    ) -> Result<Surface<'b>, RendererError> {
+        self.render_map_with_rng(
+            isomap,
+            Clock::Frame(0),
+            &mut StdRng::seed_from_u64(seed),
+            &mut |_| (),
+        )
+    }
+
+    /// Render every map in `maps` as a frame of an animation
+    ///
+    /// Map `i` is rendered with frame `i` of animated tiles, as with
+    /// [`render_map_frame`](#method.render_map_frame). Every map is rendered
+    /// with the same seed, which is the renderer's seed if it has one, or
+    /// picked at random for the whole animation otherwise. With
+    /// [`VariantMode::PositionHashed`](enum.VariantMode.html#variant.PositionHashed),
+    /// this keeps the tile variant of every block which is in more than one
+    /// map the same in all of them. In the default mode, variants only stay
+    /// the same as long as every block drawn before them stays the same.
+    pub fn render_frames<'b>(&self, maps: &[IsoMap]) -> Result<Vec<Surface<'b>>, RendererError> {
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None => rand::thread_rng().gen(),
+        };
+
+        maps.iter()
+            .enumerate()
+            .map(|(frame, isomap)| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                self.render_map_with_rng(isomap, Clock::Frame(frame as u32), &mut rng, &mut |_| ())
+            })
+            .collect()
+    }
+
+    /// Render only the floors of an `IsoMap` within the range `z`
+    ///
+    /// Blocks outside of the range are left out entirely, so the floors above
+    /// it don't hide anything, and the image is only as tall as the floors
+    /// that are drawn. This is useful for looking at the inside of a map, for
+    /// example in cutaways of the maps made by
+    /// [`TerGenTwo::generate_slices`](../map/generator/struct.TerGenTwo.html#method.generate_slices).
+    pub fn render_map_layers<'b>(
+        &self,
+        isomap: &IsoMap,
+        z: Range<usize>,
+    ) -> Result<Surface<'b>, RendererError> {
+        self.render_map(&isomap.slice_region(0..isomap.dim_x(), 0..isomap.dim_y(), z))
+    }
+
+    /// Render an `IsoMap` with an outline around every tile
+    ///
+    /// The map is rendered as by [`render_map`](#method.render_map), and then
+    /// a 1 pixel magenta outline is drawn along the inside of the rectangle
+    /// each tile was copied to. This shows exactly where the renderer puts
+    /// tiles, which helps with lining up the art in spritesheets.
+    pub fn render_debug<'b>(&self, isomap: &IsoMap) -> Result<Surface<'b>, RendererError> {
+        match self.seed {
+            Some(seed) => self.render_debug_with_rng(isomap, &mut StdRng::seed_from_u64(seed)),
+            None => self.render_debug_with_rng(isomap, &mut rand::thread_rng()),
+        }
+    }
+
+    /// Get the width and height of the image `isomap` would be rendered to
+    ///
+    /// This is the size of the surface returned by
+    /// [`render_map`](#method.render_map), so buffers can be allocated before
+    /// rendering. Sizes which don't fit in a `u32` are capped at `u32::MAX`;
+    /// rendering such a map fails with
+    /// [`RendererError::OutputTooLarge`](enum.RendererError.html#variant.OutputTooLarge).
+    pub fn output_dimensions(&self, isomap: &IsoMap) -> (u32, u32) {
+        let (surf_width, surf_height) = self.scaled_dimensions(isomap);
+        let cap = |length: u64| length.min(u64::from(u32::MAX)) as u32;
+
+        (cap(surf_width), cap(surf_height))
+    }
+
+    /// Get the width and height of the image `isomap` would be rendered to,
+    /// or an error if that's larger than an SDL surface can be
+    fn checked_dimensions(&self, isomap: &IsoMap) -> Result<(u32, u32), RendererError> {
+        let (surf_width, surf_height) = self.scaled_dimensions(isomap);
+
+        // Output surfaces have up to 4 bytes per pixel
+        let too_large = match surf_width.checked_mul(surf_height) {
+            Some(pixels) => pixels.saturating_mul(4) > MAX_OUTPUT_BYTES,
+            None => true,
+        };
+
+        if too_large {
+            return Err(RendererError::OutputTooLarge {
+                width: surf_width,
+                height: surf_height,
+            });
+        }
+
+        Ok((surf_width as u32, surf_height as u32))
+    }
+
+    /// Get the width and height of the image `isomap` would be rendered to,
+    /// without limiting them to what SDL can handle
+    fn scaled_dimensions(&self, isomap: &IsoMap) -> (u64, u64) {
+        let (surf_width, surf_height) = self.unscaled_dimensions(isomap);
+        let scale = u64::from(self.scale);
+
+        (
+            surf_width.saturating_mul(scale),
+            surf_height.saturating_mul(scale),
+        )
+    }
+
+    /// Get the width and height of the image `isomap` would be rendered to,
+    /// before scaling
+    ///
+    /// This is worked out in `u64`, so that large maps can be caught by
+    /// [`checked_dimensions`](#method.checked_dimensions) rather than
+    /// overflowing.
+    fn unscaled_dimensions(&self, isomap: &IsoMap) -> (u64, u64) {
+        let len_x = isomap.dim_x() as u64;
+        let len_y = isomap.dim_y() as u64;
+        let len_z = isomap.dim_z() as u64;
+        let width = u64::from(self.width);
+        let height = u64::from(self.height);
+
+        // Each step along the x axis moves a tile to the right by half its
+        // width, and each step along the y axis moves it to the left by the
+        // same amount. We make the surface wide enough to take the width of a
+        // floor and then add a margin
+        let surf_width = ((len_x + len_y) * width / 2) + (width * u64::from(self.margin));
+
+        // We need enough room for a single floor, then every floor stack on top
+        // of it, then some margins
+        let surf_height = self.floor_height(isomap)
+            + (u64::from(self.sides_height()) * len_z)
+            + (height * u64::from(self.margin));
+
+        // Tiles larger than the rest stick out past them, so they get some
+        // more room on top of that
+        let (extra_width, extra_height) = self.overhang();
+        let headroom = self.headroom();
+
+        (
+            surf_width + u64::from(extra_width),
+            surf_height + u64::from(extra_height) + u64::from(headroom),
+        )
+    }
+
+    /// How much wider than the renderer's tile size the largest tiles are, and
+    /// how much further below the top of their top face they reach
+    fn overhang(&self) -> (u32, u32) {
+        self.tiles
+            .values()
+            .flatten()
+            .fold((0, 0), |(width, height), tile| {
+                let below = tile.pos.height() - tile.top_offset;
+
+                (
+                    width.max(tile.pos.width().saturating_sub(self.width)),
+                    height.max(below.saturating_sub(self.height)),
+                )
+            })
+    }
+
+    /// How far above the top of their top face the tallest tiles reach
+    fn headroom(&self) -> u32 {
+        self.tiles
+            .values()
+            .flatten()
+            .map(|tile| tile.top_offset)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Pixel height of the top face of the cube. Since we're in a 2:1
+    /// projection, it's half the tile's width.
+    fn top_height(&self) -> u32 {
+        self.width / 2
+    }
+
+    /// Pixel height of the sides of the cube. This is the remainder of the
+    /// pixel height of a tile, after we account for the top face.
+    fn sides_height(&self) -> u32 {
+        self.height - self.top_height()
+    }
+
+    /// How much a single floor of `isomap` takes up in pixels, in the vertical
+    fn floor_height(&self, isomap: &IsoMap) -> u64 {
+        let len_x = isomap.dim_x() as u64;
+        let len_y = isomap.dim_y() as u64;
+
+        // Each step along the x or y axis moves a tile down by half of
+        // top_height, so going from the back corner to the front corner moves
+        // us by (len_x + len_y) halves. Then, we'll also be able to see the
+        // frontmost tile's sides, so we add sides_height.
+        ((len_x + len_y) * u64::from(self.top_height()) / 2) + u64::from(self.sides_height())
+    }
+
+    /// Render an `IsoMap` with animated tiles at `clock`, using `rng` to pick
+    /// tile variants
+    fn render_map_with_rng<'b, R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        clock: Clock,
+        rng: &mut R,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<Surface<'b>, RendererError> {
+        let (surf_width, surf_height) = self.checked_dimensions(isomap)?;
+
+        let mut out = self.new_output_surface(surf_width, surf_height)?;
+        self.render_map_into_with_rng(isomap, clock, &mut out, rng, progress)?;
+
+        Ok(out)
+    }
+
+    /// Render an `IsoMap` with tile outlines, using `rng` to pick tile variants
+    fn render_debug_with_rng<'b, R: Rng + Clone>(
+        &self,
+        isomap: &IsoMap,
+        rng: &mut R,
+    ) -> Result<Surface<'b>, RendererError> {
+        // Laying out with a copy of the RNG picks the same tiles as the render
+        let (_, _, placements) = self.layout_map(isomap, &mut rng.clone())?;
+        let mut out = self.render_map_with_rng(isomap, Clock::Frame(0), rng, &mut |_| ())?;
+
+        for (_, dest, _, _, _) in placements {
+            let (width, height) = dest.size();
+            let edges = [
+                Rect::new(dest.x(), dest.y(), width, 1),
+                Rect::new(dest.x(), dest.bottom() - 1, width, 1),
+                Rect::new(dest.x(), dest.y(), 1, height),
+                Rect::new(dest.right() - 1, dest.y(), 1, height),
+            ];
+
+            out.fill_rects(&edges, DEBUG_OUTLINE_COLOR!())?;
+        }
+
+        Ok(out)
+    }
+
+    /// Render an `IsoMap` with animated tiles at `clock` into `target`, using
+    /// `rng` to pick tile variants, and calling `progress` after every floor
+    fn render_map_into_with_rng<R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        clock: Clock,
+        target: &mut Surface,
+        rng: &mut R,
+        progress: &mut dyn FnMut(f32),
+    ) -> Result<(), RendererError> {
+        let (surf_width, surf_height, placements) = self.layout_map(isomap, rng)?;
+
+        if target.width() < surf_width || target.height() < surf_height {
+            return Err(RendererError::TargetTooSmall {
+                width: surf_width,
+                height: surf_height,
+                target_width: target.width(),
+                target_height: target.height(),
+            });
+        }
+
+        target.fill_rect(None, self.background)?;
+
+        let mut progress = FloorProgress::new(isomap.dim_z(), progress);
+
+        if self.depth_shading <= 0.0 && self.ao <= 0.0 && self.light == [1.0; 3] {
+            for (tile_sprite, tile_dest, _, z, phase) in placements {
+                progress.reach(z);
+
+                let frame = clock.frame(phase);
+                for (src, dest, _) in tile_sprite.parts(frame, tile_dest, 255, self.light) {
+                    blit_tile(&tile_sprite.sheet, src, target, dest)?;
+                }
+            }
+        } else {
+            blit_shaded(placements, clock, self.light, target, &mut progress)?;
+        }
+
+        progress.finish();
+
+        if self.axes {
+            self.draw_axes(isomap, target)?;
+        }
+
+        if self.legend {
+            self.draw_legend(isomap, target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the brightness to draw the block at `x`, `y`, `z` of `map` at,
+    /// with ambient occlusion on top of depth shading
+    fn shaded_brightness(&self, map: &ArrayView3<Block>, x: usize, y: usize, z: usize) -> u8 {
+        let brightness = self.brightness(z, map.dim().2);
+        if self.ao <= 0.0 {
+            return brightness;
+        }
+
+        let occluders = occluding_neighbours(map, x, y, z);
+        let factor = 1.0 - self.ao * occluders as f32 / 3.0;
+
+        (f32::from(brightness) * factor).round() as u8
+    }
+
+    /// Get the brightness to draw blocks on floor `z` of `len_z` floors at
+    ///
+    /// Without depth shading, everything is drawn at full brightness.
+    fn brightness(&self, z: usize, len_z: usize) -> u8 {
+        if self.depth_shading <= 0.0 || len_z < 2 {
+            return 255;
+        }
+
+        let depth = 1.0 - z as f32 / (len_z - 1) as f32;
+        let brightness = 1.0 - self.depth_shading * depth;
+
+        (brightness * 255.0).round() as u8
+    }
+
+    /// Get the position of the tile for block 0,0 on the bottom floor of
+    /// `isomap`, before scaling, with the map turned so that it's `len_y`
+    /// blocks long along the y axis, in a render `surf_height` pixels tall
+    /// after scaling
+    fn map_origin(&self, isomap: &IsoMap, len_y: usize, surf_height: u32) -> Point {
+        let floor_height = self.floor_height(isomap) as u32;
+        let unscaled_height = surf_height / self.scale;
+        let (extra_width, extra_height) = self.overhang();
+
+        // In the x axis, we start after the margin, and then leave enough room
+        // for the tiles along the y axis, which extend to the left of tile 0,0.
+        //
+        // In the y axis, we start from the bottom, go up to account for the
+        // margin, and then go up to account for the floor height.
+        //
+        // Room for oversized tiles is split evenly between the left and right,
+        // and goes entirely below, since tiles hang down from their top face.
+        let margin_x = self.width * self.margin / 2;
+        let margin_y = self.height * self.margin / 2;
+
+        Point::new(
+            (margin_x + extra_width / 2 + (len_y as u32).saturating_sub(1) * self.width / 2)
+                as i32,
+            unscaled_height as i32 - margin_y as i32 - floor_height as i32 - extra_height as i32,
+        )
+    }
+
+    /// Work out the size of the output for `isomap`, and which tiles go where
+    fn layout_map<R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        rng: &mut R,
+    ) -> Result<Layout<'_, 'a>, RendererError> {
+        // Rotating the map swaps its x and y lengths at most, which doesn't
+        // affect any of the sizes. Once the scaled size is known to fit, the
+        // unscaled sizes fit as well.
+        let dimensions = self.checked_dimensions(isomap)?;
+
+        self.layout_map_sized(isomap, dimensions, rng)
+    }
+
+    /// Work out which tiles go where in the output for `isomap`, which is
+    /// `surf_width` by `surf_height` pixels
+    fn layout_map_sized<R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        (surf_width, surf_height): (u32, u32),
+        rng: &mut R,
+    ) -> Result<Layout<'_, 'a>, RendererError> {
+        let map = oriented(&isomap.0, self.facing);
+        let (len_x, len_y, _) = map.dim();
+
+        let mut current_origin = self.map_origin(isomap, len_y, surf_height);
+
+        // Skipped blocks can be seen through, since nothing is drawn for them
+        let mut see_through = self.translucent.clone();
+        if self.missing == MissingPolicy::Skip {
+            for &block in map.iter() {
+                if !self.has_tiles(block) && !see_through.contains(&block) {
+                    see_through.push(block);
+                }
+            }
+        }
+
+        let hash_seed = self.hash_seed(rng);
+
+        let mut placements = Vec::new();
+
+        for (z, floor) in map.axis_iter(Axis(2)).enumerate() {
+            for (x, y) in floor_order(len_x, len_y) {
+                let tile = &floor[[x, y]];
+                if tile == &Block::Air {
+                    continue; // blank, do nothing
+                }
+
+                let drawn = match self.drawn_block(*tile)? {
+                    Some(block) => block,
+                    None => continue,
+                };
+
+                // The sprite is picked even for hidden blocks, so that culling
+                // doesn't affect which variants the visible blocks get
+                let edges = edge_mask(&map, x, y, z);
+                let (map_x, map_y) = unoriented(self.facing, len_x, len_y, x, y);
+                let tile_sprite =
+                    self.pick_variant(&drawn, edges, hash_seed, (map_x, map_y, z), rng)?;
+
+                // Blocks right behind the cutaway are seen from the front
+                let near_corner = near_corner_distance(len_x, len_y, x, y);
+                if near_corner < self.cutaway {
+                    continue;
+                }
+
+                let exposed = self.cutaway > 0 && near_corner == self.cutaway;
+                if self.cull && !exposed && is_occluded(&map, x, y, z, &see_through) {
+                    continue;
+                }
+
+                // Tiles of a different width are centred on the block, and
+                // tiles with something above the cube are raised so that the
+                // top face is where it would be for a plain cube
+                let (tile_width, tile_height) = tile_sprite.pos.size();
+                let tile_dest = self.get_tile_pos(current_origin, x, y).offset(
+                    (self.width as i32 - tile_width as i32) / 2,
+                    -(tile_sprite.top_offset as i32),
+                );
+                let scale = self.scale as i32;
+
+                placements.push((
+                    tile_sprite,
+                    Rect::new(
+                        tile_dest.x * scale,
+                        tile_dest.y * scale,
+                        tile_width * self.scale,
+                        tile_height * self.scale,
+                    ),
+                    self.shaded_brightness(&map, x, y, z),
+                    z,
+                    animation_phase(map_x, map_y),
+                ));
+            }
+
+            // Shift to the floor above
+            current_origin = current_origin.offset(0, -(self.sides_height() as i32));
+        }
+
+        Ok((surf_width, surf_height, placements))
+    }
+
+    /// Create a surface of the given size, in a format which can hold the
+    /// background
+    fn new_output_surface<'b>(&self, width: u32, height: u32) -> Result<Surface<'b>, RendererError> {
+        // RGB24 can't represent transparency, so we only pay for the alpha
+        // channel when the background needs it
+        let pixel_format = if self.background.a < 255 {
+            PixelFormatEnum::RGBA32
+        } else {
+            PixelFormatEnum::RGB24
+        };
+
+        Ok(Surface::new(width, height, pixel_format)?)
+    }
+
+    /// Get pixel position for a tile at map position `x_index`, `y_index`, assuming tile 0,0 is at `origin`.
+    fn get_tile_pos(&self, origin: Point, x_index: usize, y_index: usize) -> Point {
+        // Tile tops (the top surfaces of the cube) are assumed to be at a 2:1
+        // ratio, twice as wide as they are tall.
+        origin.offset(
+            (x_index as i32 - y_index as i32) * (self.width as i32 / 2),
+            (x_index as i32 + y_index as i32) * (self.width as i32 / 4),
+        )
+    }
+
+    /// Whether there are any tiles for `block`
+    fn has_tiles(&self, block: Block) -> bool {
+        match self.tiles.get(&block) {
+            Some(tiles) => !tiles.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Get the block whose tiles are drawn for `block`, according to the
+    /// policy for missing tiles, or `None` if nothing is drawn for it
+    fn drawn_block(&self, block: Block) -> Result<Option<Block>, RendererError> {
+        match self.missing {
+            _ if self.has_tiles(block) => Ok(Some(block)),
+            MissingPolicy::Skip => Ok(None),
+            MissingPolicy::Fallback(fallback) if self.has_tiles(fallback) => Ok(Some(fallback)),
+            MissingPolicy::Error | MissingPolicy::Fallback(_) => {
+                Err(RendererError::MissingTile(block))
+            }
+        }
+    }
+
+    /// Get the seed for picking variants by position, if they're picked that
+    /// way
+    ///
+    /// Hashed variants still need something to vary with between renders when
+    /// there's no seed, so they take a single number from `rng`.
+    fn hash_seed<R: Rng>(&self, rng: &mut R) -> Option<u64> {
+        match self.variants {
+            VariantMode::PositionHashed => Some(rng.gen()),
+            VariantMode::RandomStream => None,
+        }
+    }
+
+    /// Pick a tile for `tile_type`, for a block with the edges in `edges`
+    ///
+    /// With a `hash_seed`, the tile only depends on it and on `position`,
+    /// which is the position of the block in the map as it was before being
+    /// turned. Otherwise, it's picked using `rng`.
+    fn pick_variant<R: Rng>(
+        &self,
+        tile_type: &Block,
+        edges: u8,
+        hash_seed: Option<u64>,
+        position: (usize, usize, usize),
+        rng: &mut R,
+    ) -> Result<&Tile<'a>, RendererError> {
+        match hash_seed {
+            Some(seed) => {
+                let (x, y, z) = position;
+                let mut position_rng = SmallRng::seed_from_u64(position_hash(seed, x, y, z));
+                self.get_random_sprite(tile_type, edges, &mut position_rng)
+            }
+            None => self.get_random_sprite(tile_type, edges, rng),
+        }
+    }
+
+    /// Pick one of the tiles for `tile_type` at random, for a block with the
+    /// edges in `edges`
+    ///
+    /// Autotiled variants for exactly those edges are preferred. If there are
+    /// none, plain variants are used, and if there are no plain variants
+    /// either, any variant will do. Returns an error if there are no tiles for
+    /// `tile_type`.
+    fn get_random_sprite<R: Rng>(
+        &self,
+        tile_type: &Block,
+        edges: u8,
+        rng: &mut R,
+    ) -> Result<&Tile<'a>, RendererError> {
+        let all_tiles = match self.tiles.get(tile_type) {
+            Some(tiles) if !tiles.is_empty() => tiles,
+            _ => return Err(RendererError::MissingTile(*tile_type)),
+        };
+
+        let wanted = if all_tiles.iter().any(|t| t.autotile == Some(edges)) {
+            Some(Some(edges))
+        } else if all_tiles.iter().any(|t| t.autotile.is_none()) {
+            Some(None)
+        } else {
+            None
+        };
+        let potential_tiles = || {
+            all_tiles
+                .iter()
+                .filter(move |t| match wanted {
+                    Some(wanted) => t.autotile == wanted,
+                    None => true,
+                })
+        };
+
+        // Weights come from the config, and several of them can add up to more
+        // than a u32 holds
+        let total_weight: u64 = potential_tiles().map(|t| u64::from(t.weight)).sum();
+
+        // If every tile has a weight of 0, there's nothing to go by, so we fall
+        // back to treating them all equally
+        if total_weight == 0 {
+            let count = potential_tiles().count();
+            return Ok(potential_tiles().nth(rng.gen_range(0, count)).unwrap());
+        }
+
+        let mut pick = rng.gen_range(0, total_weight);
+
+        for tile in potential_tiles() {
+            let weight = u64::from(tile.weight);
+            if pick < weight {
+                return Ok(tile);
+            }
+            pick -= weight;
+        }
+
+        unreachable!("weighted pick exceeded the total weight")
+    }
+}
+
+/// Copy the tile at `src` in `sheet` to `dest` on `target`, scaling it up if
+/// `dest` is larger
+///
+/// Scaling is nearest neighbour, so at integer factors every pixel of the tile
+/// becomes a square block of pixels.
+fn blit_tile(sheet: &Surface, src: Rect, target: &mut Surface, dest: Rect) -> Result<(), String> {
+    if src.size() == dest.size() {
+        sheet.blit(src, target, dest)?;
+    } else {
+        sheet.blit_scaled(src, target, dest)?;
+    }
+
+    Ok(())
+}
+
+/// Copy the tiles in `placements` to `target`, darkened by their brightness
+/// and `light`, with animated tiles at `clock`, and reporting to `progress` as
+/// every floor is done
+fn blit_shaded(
+    placements: Vec<(&Tile, Rect, u8, usize, f64)>,
+    clock: Clock,
+    light: [f32; 3],
+    target: &mut Surface,
+    progress: &mut FloorProgress,
+) -> Result<(), String> {
+    // Shading changes the color mod of the sheets, so we shade copies of
+    // them rather than the sheets the renderer keeps
+    let mut shaded_sheets: Vec<(&Rc<Surface>, Surface)> = Vec::new();
+
+    for (tile_sprite, tile_dest, brightness, z, phase) in placements {
+        progress.reach(z);
+
+        let index = match shaded_sheets
+            .iter()
+            .position(|&(sheet, _)| Rc::ptr_eq(sheet, &tile_sprite.sheet))
+        {
+            Some(index) => index,
+            None => {
+                let sheet = &tile_sprite.sheet;
+                let mut copy = sheet.convert(&sheet.pixel_format())?;
+                copy.set_blend_mode(sheet.blend_mode())?;
+                if let Ok(key) = sheet.color_key() {
+                    copy.set_color_key(true, key)?;
+                }
+                shaded_sheets.push((sheet, copy));
+                shaded_sheets.len() - 1
+            }
+        };
+
+        let copy = &mut shaded_sheets[index].1;
+        let frame = clock.frame(phase);
+        for (src, dest, brightness) in tile_sprite.parts(frame, tile_dest, brightness, light) {
+            copy.set_color_mod(Color::RGB(brightness, brightness, brightness));
+            blit_tile(copy, src, target, dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get a view of `map` as seen from `facing`
+///
+/// The view is indexed the same way as the map, so that the renderer can draw
+/// it without knowing about the rotation.
+fn oriented(map: &Array3<Block>, facing: Facing) -> ArrayView3<'_, Block> {
+    let mut view = map.view();
+
+    match facing {
+        Facing::North => {}
+        Facing::East => {
+            view = view.permuted_axes([1, 0, 2]);
+            view.invert_axis(Axis(0));
+        }
+        Facing::South => {
+            view.invert_axis(Axis(0));
+            view.invert_axis(Axis(1));
+        }
+        Facing::West => {
+            view = view.permuted_axes([1, 0, 2]);
+            view.invert_axis(Axis(1));
+        }
+    }
+
+    view
+}
+
+/// Get the position in the original map of the block at `x`, `y` in a map
+/// turned to `facing`, which is `len_x` by `len_y` blocks after turning
+fn unoriented(facing: Facing, len_x: usize, len_y: usize, x: usize, y: usize) -> (usize, usize) {
+    match facing {
+        Facing::North => (x, y),
+        Facing::East => (y, len_x - 1 - x),
+        Facing::South => (len_x - 1 - x, len_y - 1 - y),
+        Facing::West => (len_y - 1 - y, x),
+    }
+}
+
+/// Get how many frames ahead of the rest of the map the animation of the
+/// block at `x`, `y` of the map is, when rendering at a point in time
+///
+/// Neighbouring blocks are a quarter of a frame apart, so that animations
+/// move across the map in diagonal waves.
+fn animation_phase(x: usize, y: usize) -> f64 {
+    (x + y) as f64 * 0.25
+}
+
+/// Mix a seed and a block position into a single number, for seeding the
+/// random number generator which picks the block's variant
+///
+/// This uses the finalizer of SplitMix64, so that nearby positions end up
+/// with unrelated numbers.
+fn position_hash(seed: u64, x: usize, y: usize, z: usize) -> u64 {
+    let mix = |mut n: u64| {
+        n = (n ^ (n >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        n = (n ^ (n >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        n ^ (n >> 31)
+    };
+
+    [x, y, z]
+        .iter()
+        .fold(mix(seed), |hash, &n| mix(hash ^ n as u64).wrapping_add(0x9e37_79b9_7f4a_7c15))
+}
+
+/// Get the positions in a floor of `len_x` by `len_y` blocks, in the order
+/// they need to be drawn
+///
+/// A tile can only overlap tiles which are one step away along the x or y
+/// axes, and the one in front is always the one further along. Going through
+/// the diagonals in order of `x + y` draws every tile after the tiles behind it,
+/// while the tiles within a diagonal don't overlap each other at all.
+fn floor_order(len_x: usize, len_y: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..(len_x + len_y).saturating_sub(1)).flat_map(move |diagonal| {
+        let start_x = (diagonal + 1).saturating_sub(len_y);
+        let end_x = (diagonal + 1).min(len_x);
+
+        (start_x..end_x).map(move |x| (x, diagonal - x))
+    })
+}
+
+/// Get the mask of the edges of the block at `x`, `y`, `z`
+///
+/// A side is an edge if there is nothing next to the block on that side,
+/// including at the edges of the map.
+fn edge_mask(map: &ArrayView3<Block>, x: usize, y: usize, z: usize) -> u8 {
+    // Going past 0 wraps around to a position outside of the map, which
+    // counts as empty
+    let neighbours = [
+        (Edge::North, x, y.wrapping_sub(1)),
+        (Edge::East, x + 1, y),
+        (Edge::South, x, y + 1),
+        (Edge::West, x.wrapping_sub(1), y),
+    ];
+
+    neighbours
+        .iter()
+        .filter(|&&(_, nx, ny)| match map.get([nx, ny, z]) {
+            Some(block) => block == &Block::Air,
+            None => true,
+        }).fold(0, |mask, &(edge, _, _)| mask | edge.bit())
+}
+
+/// Check whether the block at `x`, `y`, `z` is completely covered by the blocks
+/// drawn after it
+///
+/// The three visible faces of a block are shared with the blocks in front of
+/// it along the x and y axes, and the block above it, so if all three are
+/// present and not `translucent`, none of the block will be visible.
+fn is_occluded(map: &ArrayView3<Block>, x: usize, y: usize, z: usize, translucent: &[Block]) -> bool {
+    let (len_x, len_y, len_z) = map.dim();
+    let occludes = |block: Block| block != Block::Air && !translucent.contains(&block);
+
+    x + 1 < len_x
+        && y + 1 < len_y
+        && z + 1 < len_z
+        && occludes(map[[x + 1, y, z]])
+        && occludes(map[[x, y + 1, z]])
+        && occludes(map[[x, y, z + 1]])
+}
+
+/// Get the number of steps along the x and y axes from `x`, `y` to the corner
+/// of a `len_x` by `len_y` floor which is nearest the viewer, at the bottom of
+/// the screen
+fn near_corner_distance(len_x: usize, len_y: usize, x: usize, y: usize) -> usize {
+    (len_x - 1 - x) + (len_y - 1 - y)
+}
+
+/// Count the solid blocks one floor above the block at `x`, `y`, `z` which
+/// are right behind it on screen, that is, up and to the left, up and to the
+/// right, and straight up
+fn occluding_neighbours(map: &ArrayView3<Block>, x: usize, y: usize, z: usize) -> usize {
+    if z + 1 >= map.dim().2 {
+        return 0;
+    }
+
+    let behind = [
+        (x.checked_sub(1), Some(y)),
+        (Some(x), y.checked_sub(1)),
+        (x.checked_sub(1), y.checked_sub(1)),
+    ];
+
+    behind
+        .iter()
+        .filter(|&&position| match position {
+            (Some(x), Some(y)) => map[[x, y, z + 1]].is_solid(),
+            _ => false,
+        })
+        .count()
+}
+
+/// Copy the pixels of an RGB24 or RGBA32 surface out into a tightly packed
+/// RGBA8 buffer
+///
+/// Rows of a surface can be padded, so each row is copied separately, skipping
+/// any bytes beyond the pitch.
+fn surface_to_rgba(surf: &Surface) -> Vec<u8> {
+    let width = surf.width() as usize;
+    let height = surf.height() as usize;
+    let pitch = surf.pitch() as usize;
+    let has_alpha = surf.pixel_format_enum() == PixelFormatEnum::RGBA32;
+
+    let mut buffer = Vec::with_capacity(width * height * 4);
+
+    surf.with_lock(|pixels| {
+        for row in pixels.chunks(pitch).take(height) {
+            if has_alpha {
+                buffer.extend_from_slice(&row[..width * 4]);
+            } else {
+                for rgb in row[..width * 3].chunks(3) {
+                    buffer.extend_from_slice(rgb);
+                    buffer.push(255);
+                }
+            }
+        }
+    });
+
+    buffer
+}
+
+/// Parse a `Renderer` from a TOML configuration, the same as
+/// [`Renderer::from_config_str`](struct.Renderer.html#method.from_config_str)
+///
+/// The lifetime of the resulting `Renderer` is not tied to the input string,
+/// so `"...".parse::<Renderer>()` can be used anywhere `from_config_str` can.
+impl<'a> FromStr for Renderer<'a> {
+    type Err = ConfigLoadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_config_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) fn test_config_path() -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("assets/test-tiles.toml");
+        path
+    }
+
+    /// A renderer for 24x26 tiles with every setting at its default
+    pub(super) fn test_renderer<'a>(tiles: HashMap<Block, Vec<Tile<'a>>>) -> Renderer<'a> {
+        Renderer {
+            width: 24,
+            height: 26,
+            tiles,
+            background: DEFAULT_BACKGROUND_COLOR!(),
+            seed: None,
+            cull: true,
+            translucent: Vec::new(),
+            facing: Facing::North,
+            scale: 1,
+            depth_shading: 0.0,
+            margin: Renderer::DEFAULT_MARGIN,
+            legend: false,
+            light: [1.0; 3],
+            missing: MissingPolicy::Error,
+            variants: VariantMode::RandomStream,
+            ao: 0.0,
+            axes: false,
+            cutaway: 0,
+        }
+    }
+
+    /// A renderer drawing every block in its default color
+    pub(super) fn solid_renderer<'a>() -> Renderer<'a> {
+        let colors = Block::into_enum_iter()
+            .filter(|block| block != &Block::Air)
+            .map(|block| (block, block.default_color()))
+            .collect();
+
+        Renderer::solid_colors(24, 26, colors).unwrap()
+    }
+
+    #[test]
     fn load_config() {
-        let config_toml = include_str!("../../assets/test-tiles.toml");
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+
+        assert_eq!(rconfig.width, 24);
+        match rconfig.tiles.get(&Block::Rock) {
+            Some(t) => assert_eq!(t[0].pos, Rect::new(0, 0, 24, 26)),
+            None => panic!(),
+        }
+    }
+
+    #[test]
+    fn load_config_with_base() {
+        let config = fs::read_to_string(test_config_path())
+            .unwrap()
+            .replace("base_path = \".\"", "");
+        let mut base = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        base.push("assets");
+
+        let rconfig = Renderer::from_config_str_with_base(&config, &base).unwrap();
+
+        assert_eq!(rconfig.tiles[&Block::Rock].len(), 2);
+    }
+
+    #[test]
+    fn introspection() {
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+        let counts = rconfig.tile_counts();
+
+        assert_eq!(rconfig.tile_size(), (24, 26));
+        assert_eq!(counts[&Block::Rock], 2);
+        assert_eq!(counts[&Block::Water], 1);
+        assert!(!counts.contains_key(&Block::Air));
+    }
+
+    #[test]
+    fn config_from_reader() {
+        let path = test_config_path();
+        let file = fs::File::open(&path).unwrap();
+        let rconfig = Renderer::from_config_reader(file, path.parent().unwrap()).unwrap();
+        assert_eq!(rconfig.tile_counts()[&Block::Rock], 2);
+
+        let not_utf8: &[u8] = &[0xff, 0xfe, 0xfd];
+        match Renderer::from_config_reader(not_utf8, &path) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::FileReadError),
+            Ok(_) => panic!(),
+        }
+
+        match Renderer::from_config_reader("width = ".as_bytes(), &path) {
+            Err(e) => {
+                assert_eq!(e.kind(), &ConfigLoadErrorKind::TomlParseError { filename: None })
+            }
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn missing_config_file() {
+        let mut path = test_config_path();
+        path.set_file_name("does-not-exist.toml");
+
+        match Renderer::from_config_file(&path) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::FileReadError),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn errors_name_file() {
+        use std::env;
+
+        let config = format!(
+            r#"
+            width = 24
+            height = 26
+            base_path = "{}/assets"
+
+            [[files]]
+            filename = "does-not-exist.png"
+            "#,
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        let error = match Renderer::from_config_str(&config) {
+            Err(e) => e,
+            Ok(_) => panic!(),
+        };
+        assert!(error.to_string().ends_with("(in does-not-exist.png)"));
+        match error.into_kind() {
+            ConfigLoadErrorKind::SDLError { filename, .. } => {
+                assert_eq!(filename, Some("does-not-exist.png".to_string()))
+            }
+            kind => panic!("unexpected error {:?}", kind),
+        }
+
+        let mut path = env::temp_dir();
+        path.push("cubeglobe-bad-config.toml");
+        fs::write(&path, "width = ").unwrap();
+        let result = Renderer::from_config_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        match result {
+            Err(e) => assert_eq!(
+                e.into_kind(),
+                ConfigLoadErrorKind::TomlParseError {
+                    filename: Some(path.display().to_string())
+                }
+            ),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn tile_out_of_bounds() {
+        let config = format!(
+            r#"
+            width = 25
+            height = 26
+            base_path = "{}/assets"
+
+            [[files]]
+            filename = "twocubetall.png"
+
+                [[files.tiles]]
+                kind = "Rock"
+                x = 24
+            "#,
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        match Renderer::from_config_str(&config) {
+            Err(e) => assert_eq!(
+                e.kind(),
+                &ConfigLoadErrorKind::TileOutOfBounds {
+                    filename: "twocubetall.png".to_string(),
+                    block: Block::Rock,
+                }
+            ),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn bad_tile_width() {
+        let colors = || {
+            Block::into_enum_iter()
+                .filter(|block| block != &Block::Air)
+                .map(|block| (block, block.default_color()))
+                .collect()
+        };
+
+        for &width in &[22, 25] {
+            match Renderer::solid_colors(width, 26, colors()) {
+                Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::BadTileWidth(width)),
+                Ok(_) => panic!(),
+            }
+        }
+
+        assert!(Renderer::solid_colors(28, 26, colors()).is_ok());
+    }
+
+    #[test]
+    fn per_file_tile_size() {
+        let config = format!(
+            r#"
+            width = 24
+            height = 13
+            base_path = "{}/assets"
+
+            [[files]]
+            filename = "twocubetall.png"
+            height = 26
+
+                [[files.tiles]]
+                kind = "Rock"
+
+                [[files.grid]]
+                kind = "Water"
+                count = 2
+
+            [[files]]
+            filename = "twocubetall.png"
+
+                [[files.tiles]]
+                kind = "Soil"
+
+                [[files.tiles]]
+                kind = "Grass"
+
+                [[files.tiles]]
+                kind = "Sand"
+
+                [[files.tiles]]
+                kind = "Snow"
+            "#,
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        let rconfig = Renderer::from_config_str(&config).unwrap();
+
+        assert_eq!(rconfig.tile_size(), (24, 13));
+        assert_eq!(rconfig.tiles[&Block::Rock][0].pos.size(), (24, 26));
+        assert_eq!(rconfig.tiles[&Block::Water][1].pos, Rect::new(24, 0, 24, 26));
+        assert_eq!(rconfig.tiles[&Block::Sand][0].pos.size(), (24, 13));
+        assert_eq!(rconfig.overhang(), (0, 13));
+    }
+
+    #[test]
+    fn grid_expands() {
+        let grid = GridDef {
+            kind: Block::Rock,
+            x: Some(2),
+            y: None,
+            count: 5,
+            columns: Some(2),
+            stride_x: None,
+            stride_y: Some(30),
+            weight: Some(3),
+        };
+
+        let positions: Vec<(Option<i32>, Option<i32>)> =
+            grid.expand(24, 26).iter().map(|t| (t.x, t.y)).collect();
+
+        assert_eq!(
+            positions,
+            vec![
+                (Some(2), Some(0)),
+                (Some(26), Some(0)),
+                (Some(2), Some(30)),
+                (Some(26), Some(30)),
+                (Some(2), Some(60)),
+            ]
+        );
+    }
+
+    #[test]
+    fn load_grid() {
+        let config = format!(
+            r#"
+            width = 24
+            height = 26
+            base_path = "{}/assets"
+
+            [[files]]
+            filename = "twocubetall.png"
+
+                [[files.grid]]
+                kind = "Rock"
+                count = 2
+
+                [[files.tiles]]
+                kind = "Water"
+
+                [[files.grid]]
+                kind = "Soil"
+                x = 24
+                count = 1
+
+                [[files.grid]]
+                kind = "Grass"
+                count = 1
+
+                [[files.grid]]
+                kind = "Sand"
+                count = 1
+
+                [[files.grid]]
+                kind = "Snow"
+                count = 1
+            "#,
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        let rconfig = Renderer::from_config_str(&config).unwrap();
+        let rock: Vec<Rect> = rconfig.tiles[&Block::Rock].iter().map(|t| t.pos).collect();
+
+        assert_eq!(rock, vec![Rect::new(0, 0, 24, 26), Rect::new(24, 0, 24, 26)]);
+        assert_eq!(rconfig.tiles[&Block::Soil][0].pos, Rect::new(24, 0, 24, 26));
+    }
+
+    #[test]
+    fn seeded_render() {
+        use map::generator::{Generator, TestingGenerator};
+
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+        let iso_map = TestingGenerator { dim: 8 }.generate();
+
+        let pixels = |seed: u64| {
+            rconfig
+                .render_map_seeded(&iso_map, seed)
+                .unwrap()
+                .with_lock(|p| p.to_vec())
+        };
+
+        assert_eq!(pixels(42), pixels(42));
+        assert_ne!(pixels(42), pixels(43));
+    }
+
+    #[test]
+    fn renderer_seed() {
+        use map::generator::{Generator, TestingGenerator};
+
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(7);
+        let iso_map = TestingGenerator { dim: 8 }.generate();
+
+        let (first, _, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+        let (second, _, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn weighted_pick() {
+        let sheet = Rc::new(Surface::new(48, 26, PixelFormatEnum::RGB24).unwrap());
+        let tile = |x, weight| Tile {
+            sheet: Rc::clone(&sheet),
+            pos: Rect::new(x, 0, 24, 26),
+            weight,
+            frames: 1,
+            stride: 24,
+            autotile: None,
+            faces: None,
+            top_offset: 0,
+        };
+
+        let mut tiles = HashMap::new();
+        tiles.insert(Block::Rock, vec![tile(0, 0), tile(24, 1)]);
+        tiles.insert(Block::Grass, vec![tile(0, 3_000_000_000), tile(24, 3_000_000_000)]);
+
+        let rconfig = test_renderer(tiles);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let picked = rconfig.get_random_sprite(&Block::Rock, 0, &mut rng).unwrap();
+            assert_eq!(picked.pos.x(), 24);
+        }
+
+        // Weights which add up to more than a u32 can hold
+        let picks: Vec<i32> = (0..100)
+            .map(|_| {
+                rconfig
+                    .get_random_sprite(&Block::Grass, 0, &mut rng)
+                    .unwrap()
+                    .pos
+                    .x()
+            })
+            .collect();
+        assert!(picks.contains(&0) && picks.contains(&24));
+    }
+
+    #[test]
+    fn edges_of_blocks() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Grass);
+        iso_map.0[[1, 1, 1]] = Block::Grass;
+        let view = iso_map.0.view();
+
+        assert_eq!(edge_mask(&view, 1, 1, 0), 0);
+        assert_eq!(edge_mask(&view, 1, 1, 1), 0b1111);
+        assert_eq!(edge_mask(&view, 0, 0, 0), Edge::North.bit() | Edge::West.bit());
+        assert_eq!(edge_mask(&view, 2, 1, 0), Edge::East.bit());
+    }
+
+    #[test]
+    fn autotile_preferred() {
+        let sheet = Rc::new(Surface::new(72, 26, PixelFormatEnum::RGB24).unwrap());
+        let tile = |x, autotile| Tile {
+            sheet: Rc::clone(&sheet),
+            pos: Rect::new(x, 0, 24, 26),
+            weight: 1,
+            frames: 1,
+            stride: 24,
+            autotile,
+            faces: None,
+            top_offset: 0,
+        };
+
+        let mut tiles = HashMap::new();
+        tiles.insert(
+            Block::Grass,
+            vec![tile(0, None), tile(24, Some(Edge::North.bit())), tile(48, Some(0))],
+        );
+        tiles.insert(Block::Rock, vec![tile(24, Some(Edge::North.bit()))]);
+
+        let rconfig = test_renderer(tiles);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut pick = |block, edges| {
+            rconfig
+                .get_random_sprite(&block, edges, &mut rng)
+                .unwrap()
+                .pos
+                .x()
+        };
+
+        for _ in 0..20 {
+            assert_eq!(pick(Block::Grass, Edge::North.bit()), 24);
+            assert_eq!(pick(Block::Grass, 0), 48);
+            assert_eq!(pick(Block::Grass, Edge::South.bit()), 0);
+
+            // Without plain variants, anything goes
+            assert_eq!(pick(Block::Rock, Edge::South.bit()), 24);
+        }
+    }
+
+    #[test]
+    fn tall_tiles_hang_down() {
+        let sheet = Rc::new(Surface::new(48, 52, PixelFormatEnum::RGB24).unwrap());
+        let tile = |x, height| Tile {
+            sheet: Rc::clone(&sheet),
+            pos: Rect::new(x, 0, 24, height),
+            weight: 1,
+            frames: 1,
+            stride: 24,
+            autotile: None,
+            faces: None,
+            top_offset: 0,
+        };
+        let renderer = |tall| {
+            let mut tiles = HashMap::new();
+            tiles.insert(Block::Rock, vec![tile(0, 26)]);
+            tiles.insert(Block::Grass, vec![tile(24, if tall { 52 } else { 26 })]);
+
+            test_renderer(tiles)
+        };
+
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[1, 1, 0]] = Block::Grass;
+
+        let (short, tall) = (renderer(false), renderer(true));
+        let (short_width, short_height) = short.output_dimensions(&iso_map);
+        assert_eq!(tall.output_dimensions(&iso_map), (short_width, short_height + 26));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (_, _, short_placements) = short.layout_map(&iso_map, &mut rng).unwrap();
+        let (_, _, tall_placements) = tall.layout_map(&iso_map, &mut rng).unwrap();
+
+        // Every tile is in the same place, with the extra room below them
+        for (&(_, short_dest, _, _, _), &(_, tall_dest, _, _, _)) in
+            short_placements.iter().zip(&tall_placements)
+        {
+            assert_eq!(short_dest.top_left(), tall_dest.top_left());
+        }
+        assert_eq!(tall_placements[1].1.height(), 52);
+    }
+
+    #[test]
+    fn cutaway_corner() {
+        let rconfig = solid_renderer();
+
+        let mut iso_map = IsoMap::new_empty_dims(4, 4, 2);
+        iso_map.0.fill(Block::Rock);
+
+        let drawn = |rconfig: &Renderer| {
+            let (_, _, placements) = rconfig
+                .layout_map(&iso_map, &mut StdRng::seed_from_u64(0))
+                .unwrap();
+            placements.len()
+        };
+
+        // Only the bottom floor has blocks hidden by the ones around them
+        assert_eq!(drawn(&rconfig), 32 - 9);
+
+        // Three columns are cut away, and the block behind them on the
+        // bottom floor is no longer hidden
+        let rconfig = rconfig.set_cutaway(2);
+        assert_eq!(drawn(&rconfig), 32 - 6 - 8);
+        assert_eq!(near_corner_distance(4, 4, 2, 2), 2);
+
+        let rconfig = rconfig.set_facing(Facing::West);
+        assert_eq!(drawn(&rconfig), 32 - 6 - 8);
+    }
+
+    #[test]
+    fn raised_tiles() {
+        let config = |top_offset| {
+            format!(
+                r#"
+                width = 24
+                height = 13
+                base_path = "{}/assets"
+
+                [[files]]
+                filename = "twocubetall.png"
+
+                    [[files.tiles]]
+                    kind = "Rock"
+
+                    [[files.tiles]]
+                    kind = "Soil"
+
+                    [[files.tiles]]
+                    kind = "Sand"
+
+                    [[files.tiles]]
+                    kind = "Snow"
+
+                    [[files.tiles]]
+                    kind = "Water"
+
+                [[files]]
+                filename = "twocubetall.png"
+                height = 26
+
+                    [[files.tiles]]
+                    kind = "Grass"
+                    top_offset = {}
+                "#,
+                env!("CARGO_MANIFEST_DIR"),
+                top_offset
+            )
+        };
+
+        let flat = Renderer::from_config_str(&config(0)).unwrap();
+        let raised = Renderer::from_config_str(&config(13)).unwrap();
+        assert_eq!(flat.overhang(), (0, 13));
+        assert_eq!((raised.overhang(), raised.headroom()), ((0, 0), 13));
+
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+        iso_map.0[[1, 1, 0]] = Block::Grass;
+        assert_eq!(
+            raised.output_dimensions(&iso_map),
+            flat.output_dimensions(&iso_map)
+        );
+
+        // The room for the grass moves from below the map to above it, and
+        // the grass tile sticks up above its block by as much
+        let mut rng = StdRng::seed_from_u64(0);
+        let (_, _, flat_placements) = flat.layout_map(&iso_map, &mut rng).unwrap();
+        let (_, _, raised_placements) = raised.layout_map(&iso_map, &mut rng).unwrap();
+        assert_eq!(flat_placements[0].1.y() + 13, raised_placements[0].1.y());
+        assert_eq!(flat_placements[1].1.y(), raised_placements[1].1.y());
+
+        match Renderer::from_config_str(&config(26)) {
+            Err(e) => assert_eq!(
+                e.kind(),
+                &ConfigLoadErrorKind::TopOffsetOutOfBounds(Block::Grass)
+            ),
+            Ok(_) => panic!(),
+        }
+    }
+
+    #[test]
+    fn frames_loop() {
+        let sheet = Rc::new(Surface::new(96, 26, PixelFormatEnum::RGB24).unwrap());
+        let tile = Tile {
+            sheet,
+            pos: Rect::new(0, 0, 24, 26),
+            weight: 1,
+            frames: 4,
+            stride: 24,
+            autotile: None,
+            faces: None,
+            top_offset: 0,
+        };
+
+        assert_eq!(tile.frame_pos(0), Rect::new(0, 0, 24, 26));
+        assert_eq!(tile.frame_pos(3), Rect::new(72, 0, 24, 26));
+        assert_eq!(tile.frame_pos(5), Rect::new(24, 0, 24, 26));
+    }
+
+    #[test]
+    fn frames_follow_time() {
+        // A fixed frame is the same everywhere, while time is offset by phase
+        assert_eq!(Clock::Frame(3).frame(animation_phase(5, 2)), 3);
+        assert_eq!(Clock::Time(3.0).frame(animation_phase(0, 0)), 3);
+        assert_eq!(Clock::Time(3.0).frame(animation_phase(5, 2)), 4);
+        assert_eq!(Clock::Time(2.9).frame(animation_phase(0, 1)), 3);
+        assert_eq!(Clock::Time(-2.0).frame(0.0), 0);
+
+        // Phases are by position in the map, whichever way it faces
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0[[2, 0, 0]] = Block::Water;
+        for facing in Facing::into_enum_iter() {
+            let rconfig = Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .set_facing(facing);
+            let mut rng = StdRng::seed_from_u64(1);
+            let (_, _, placements) = rconfig.layout_map(&iso_map, &mut rng).unwrap();
+            assert_eq!(placements[0].4, 0.5);
+        }
+    }
+
+    #[test]
+    fn margins() {
+        let rconfig = test_renderer(HashMap::new());
+        let iso_map = IsoMap::new_empty_dims(3, 5, 2);
+
+        let (width, height) = rconfig.output_dimensions(&iso_map);
+        let rconfig = rconfig.set_margin(0);
+        assert_eq!(rconfig.output_dimensions(&iso_map), (width - 48, height - 52));
+        let rconfig = rconfig.set_margin(5);
+        assert_eq!(rconfig.output_dimensions(&iso_map), (width + 72, height + 78));
+    }
+
+    #[test]
+    fn empty_tiles_are_missing() {
+        let mut tiles = HashMap::new();
+        for block in Block::into_enum_iter() {
+            tiles.insert(block, Vec::new());
+        }
+
+        match Renderer::from_tiles(24, 26, tiles) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::MissingBlock(Block::Rock)),
+            Ok(_) => panic!(),
+        }
+
+        // A file can contribute no tiles at all
+        let config = format!(
+            r#"
+            width = 24
+            height = 26
+            base_path = "{}/assets"
+
+            [[files]]
+            filename = "twocubetall.png"
+            tiles = []
+
+                [[files.grid]]
+                kind = "Snow"
+                count = 0
+
+            [[files]]
+            filename = "twocubetall.png"
+            tiles = [
+                {{ kind = "Rock" }},
+                {{ kind = "Grass" }},
+                {{ kind = "Soil" }},
+                {{ kind = "Water" }},
+                {{ kind = "Sand" }},
+            ]
+            "#,
+            env!("CARGO_MANIFEST_DIR")
+        );
+
+        match Renderer::from_config_str(&config) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::MissingBlock(Block::Snow)),
+            Ok(_) => panic!(),
+        }
+    }
 
-        // TODO: Using cwd here is probably a bit flaky
-        let mut assets_dir = env::current_dir().unwrap();
-        assets_dir.push("assets/");
+    #[test]
+    fn sheets_from_bytes() {
+        let config = fs::read_to_string(test_config_path()).unwrap();
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("assets/twocubetall.png");
+        let bytes = fs::read(&path).unwrap();
 
-        let rconfig = Renderer::from_config_str(config_toml).unwrap();
+        let mut sheets: HashMap<String, &[u8]> = HashMap::new();
+        match Renderer::from_config_str_with_sheets(&config, &sheets) {
+            Err(e) => assert_eq!(
+                e.kind(),
+                &ConfigLoadErrorKind::MissingSheet("twocubetall.png".to_string())
+            ),
+            Ok(_) => panic!("expected MissingSheet"),
+        }
 
-        assert_eq!(rconfig.width, 24);
-        match rconfig.tiles.get(&Block::Rock) {
-            Some(t) => assert_eq!(t[0].pos, Rect::new(0, 0, 24, 26)),
-            None => panic!(),
+        sheets.insert("twocubetall.png".to_string(), &bytes);
+        let from_bytes = Renderer::from_config_str_with_sheets(&config, &sheets).unwrap();
+        let from_file = Renderer::from_config_file(&test_config_path()).unwrap();
+        assert_eq!(from_bytes.tile_counts(), from_file.tile_counts());
+        assert_eq!(
+            from_bytes.tiles[&Block::Rock][0].sheet.size(),
+            from_file.tiles[&Block::Rock][0].sheet.size()
+        );
+    }
+
+    #[test]
+    fn color_keys() {
+        let mut base = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        base.push("assets");
+        let config = fs::read_to_string(test_config_path()).unwrap();
+        let rock_key = |config: &str| {
+            let rconfig = Renderer::from_config_str_with_base(config, &base).unwrap();
+            let key = rconfig.tiles[&Block::Rock][0].sheet.color_key();
+            key.ok()
+        };
+
+        // Without a key, nothing is keyed out
+        assert_eq!(rock_key(&config), None);
+
+        let global = config.replace(
+            "base_path = \".\"",
+            "base_path = \".\"\ncolor_key = [255, 0, 255]",
+        );
+        assert_eq!(rock_key(&global), Some(Color::RGB(255, 0, 255)));
+
+        // A key for the file takes precedence
+        let per_file = global.replace(
+            "filename = \"twocubetall.png\"",
+            "filename = \"twocubetall.png\"\ncolor_key = [1, 2, 3]",
+        );
+        assert_eq!(rock_key(&per_file), Some(Color::RGB(1, 2, 3)));
+    }
+
+    #[test]
+    fn custom_blocks() {
+        let config = fs::read_to_string(test_config_path()).unwrap().replace(
+            "kind = \"Snow\"",
+            "kind = \"Snow\"\n\n[[files.tiles]]\nkind = { Custom = 7 }",
+        );
+        let mut base = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        base.push("assets");
+
+        let rconfig = Renderer::from_config_str_with_base(&config, &base).unwrap();
+        assert_eq!(rconfig.tile_counts()[&Block::Custom(7)], 1);
+
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 0]] = Block::Custom(7);
+        assert!(rconfig.render_map(&iso_map).is_ok());
+
+        iso_map.0[[1, 0, 0]] = Block::Custom(8);
+        match rconfig.render_map(&iso_map) {
+            Err(RendererError::MissingTile(block)) => assert_eq!(block, Block::Custom(8)),
+            _ => panic!("expected MissingTile"),
+        }
+    }
+
+    #[test]
+    fn unoriented_positions() {
+        let mut iso_map = IsoMap::new_empty_dims(3, 2, 1);
+        for (i, block) in iso_map.0.iter_mut().enumerate() {
+            *block = Block::Custom(i as u8);
+        }
+
+        for facing in Facing::into_enum_iter() {
+            let map = oriented(&iso_map.0, facing);
+            let (len_x, len_y, _) = map.dim();
+
+            for ((x, y, z), block) in map.indexed_iter() {
+                let (map_x, map_y) = unoriented(facing, len_x, len_y, x, y);
+                assert_eq!(iso_map.0[[map_x, map_y, z]], *block);
+            }
+        }
+    }
+
+    #[test]
+    fn position_hashed_variants() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(3)
+            .set_variant_mode(VariantMode::PositionHashed);
+        let picks = |iso_map: &IsoMap| -> Vec<(Rect, Rect)> {
+            let mut rng = StdRng::seed_from_u64(3);
+            let (_, _, placements) = rconfig.layout_map(iso_map, &mut rng).unwrap();
+            placements
+                .iter()
+                .filter(|placement| placement.3 == 0)
+                .map(|&(tile, dest, _, _, _)| (tile.pos, dest))
+                .collect()
+        };
+
+        let mut iso_map = IsoMap::new_empty(8);
+        for x in 0..8 {
+            for y in 0..8 {
+                iso_map.0[[x, y, 0]] = Block::Rock;
+            }
+        }
+        let before = picks(&iso_map);
+        assert!(before.iter().any(|&(pos, _)| pos != before[0].0));
+
+        // With a stream of random numbers, changing the block drawn first
+        // would change the picks for every block after it
+        iso_map.0[[0, 0, 0]] = Block::Sand;
+        let after = picks(&iso_map);
+        assert_eq!(&after[1..], &before[1..]);
+    }
+
+    #[test]
+    fn frames_keep_variants() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .set_variant_mode(VariantMode::PositionHashed);
+
+        let mut first = IsoMap::new_empty(6);
+        for x in 0..6 {
+            for y in 0..6 {
+                first.0[[x, y, 0]] = Block::Rock;
+            }
+        }
+        let mut second = first.clone();
+        second.0[[0, 0, 0]] = Block::Sand;
+
+        // Without a seed, both frames still share one, so only the front
+        // corner at the back changes between them
+        let frames = rconfig.render_frames(&[first, second]).unwrap();
+        let (a, b) = (surface_to_rgba(&frames[0]), surface_to_rgba(&frames[1]));
+        let row_len = frames[0].width() as usize * 4;
+        let changed_rows: Vec<usize> = a
+            .chunks(row_len)
+            .zip(b.chunks(row_len))
+            .enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(|(row, _)| row)
+            .collect();
+
+        assert!(changed_rows.len() <= 26);
+    }
+
+    #[test]
+    fn missing_policy() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(4);
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+
+        let expected = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+
+        iso_map.0[[1, 1, 0]] = Block::Custom(1);
+        assert!(rconfig.render_map(&iso_map).is_err());
+
+        let rconfig = rconfig.set_missing_policy(MissingPolicy::Skip);
+        let skipped = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        assert_eq!(skipped, expected);
+
+        // The custom block is drawn as rock, so it's the same as two rocks
+        iso_map.0[[1, 1, 0]] = Block::Rock;
+        let expected = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        iso_map.0[[1, 1, 0]] = Block::Custom(1);
+
+        let rconfig = rconfig.set_missing_policy(MissingPolicy::Fallback(Block::Rock));
+        let substituted = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        assert_eq!(substituted, expected);
+
+        let rconfig = rconfig.set_missing_policy(MissingPolicy::Fallback(Block::Custom(2)));
+        match rconfig.render_map(&iso_map) {
+            Err(RendererError::MissingTile(block)) => assert_eq!(block, Block::Custom(1)),
+            _ => panic!("expected MissingTile"),
+        }
+    }
+
+    #[test]
+    fn progress_per_floor() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(2);
+
+        // Shaded renders take a different path, which reports the same way
+        for &shading in &[0.0, 0.5] {
+            let rconfig = Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .with_seed(2)
+                .set_depth_shading(shading);
+            let mut iso_map = IsoMap::new_empty_dims(3, 3, 5);
+            iso_map.0.slice_mut(s![.., .., 0..2]).fill(Block::Rock);
+
+            let mut reports = Vec::new();
+            let rendered = rconfig
+                .render_map_with_progress(&iso_map, |done| reports.push(done))
+                .unwrap();
+
+            // Floors without any blocks still count
+            assert_eq!(reports, vec![0.2, 0.4, 0.6, 0.8, 1.0]);
+            assert_eq!(
+                surface_to_rgba(&rendered),
+                surface_to_rgba(&rconfig.render_map(&iso_map).unwrap())
+            );
+        }
+
+        let mut called = false;
+        rconfig
+            .render_map_with_progress(&IsoMap::new_empty_dims(2, 2, 0), |_| called = true)
+            .unwrap();
+        assert!(!called);
+    }
+
+    #[test]
+    fn missing_tile_is_error() {
+        let rconfig = test_renderer(HashMap::new());
+
+        let mut iso_map = IsoMap::new_empty(2);
+        iso_map.0[[0, 0, 0]] = Block::Sand;
+
+        match rconfig.render_map(&iso_map) {
+            Err(RendererError::MissingTile(block)) => assert_eq!(block, Block::Sand),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn output_too_large() {
+        let rconfig = test_renderer(HashMap::new());
+        let iso_map = IsoMap::new_empty(2);
+        assert!(rconfig.checked_dimensions(&iso_map).is_ok());
+
+        // Large enough to overflow a u32 once scaled
+        let rconfig = rconfig.set_scale(u32::MAX);
+        assert_eq!(rconfig.output_dimensions(&iso_map), (u32::MAX, u32::MAX));
+
+        match rconfig.render_map(&iso_map) {
+            Err(RendererError::OutputTooLarge { width, height }) => {
+                assert_eq!(width, 96 * u64::from(u32::MAX));
+                assert_eq!(height, 118 * u64::from(u32::MAX));
+            }
+            _ => panic!("expected OutputTooLarge"),
+        }
+
+        // Fits in a u32, but not in a surface
+        let rconfig = rconfig.set_scale(1000);
+        match rconfig.render_map(&iso_map) {
+            Err(RendererError::OutputTooLarge { width, height }) => {
+                assert_eq!((width, height), (96_000, 118_000))
+            }
+            _ => panic!("expected OutputTooLarge"),
+        }
+    }
+
+    #[test]
+    fn culling_is_invisible() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(3);
+
+        let mut iso_map = IsoMap::new_empty(6);
+        iso_map.0.fill(Block::Rock);
+
+        let (culled, _, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+
+        let rconfig = rconfig.set_cull(false);
+        let (unculled, _, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+
+        assert_eq!(culled, unculled);
+    }
+
+    #[test]
+    fn non_cube_not_clipped() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .set_background(Color::RGBA(0, 0, 0, 0));
+
+        let mut iso_map = IsoMap::new_empty_dims(3, 7, 2);
+        iso_map.0.fill(Block::Rock);
+
+        let (buffer, width, height) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+        let alpha = |x: u32, y: u32| buffer[((y * width + x) * 4 + 3) as usize];
+
+        // Every tile is drawn, so something ends up on the surface...
+        assert!((0..height).any(|y| (0..width).any(|x| alpha(x, y) != 0)));
+
+        // ...but the margins on every side stay empty, so nothing was drawn
+        // past the edges
+        for y in 0..height {
+            for x in (0..rconfig.width).chain((width - rconfig.width)..width) {
+                assert_eq!(alpha(x, y), 0);
+            }
+        }
+        for x in 0..width {
+            for y in (0..rconfig.height).chain((height - rconfig.height)..height) {
+                assert_eq!(alpha(x, y), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn output_dimensions_match_render() {
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+
+        for &(x, y, z) in &[(1, 1, 1), (4, 4, 4), (3, 7, 2), (10, 2, 6), (0, 0, 0)] {
+            let iso_map = IsoMap::new_empty_dims(x, y, z);
+            let surface = rconfig.render_map(&iso_map).unwrap();
+
+            assert_eq!(rconfig.output_dimensions(&iso_map), surface.size());
+        }
+    }
+
+    #[test]
+    fn render_layers() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(3);
+        let mut iso_map = IsoMap::new_empty_dims(4, 4, 8);
+        iso_map.0.slice_mut(s![.., .., 0..6]).fill(Block::Rock);
+
+        let layers = rconfig.render_map_layers(&iso_map, 1..4).unwrap();
+        let sliced = iso_map.slice_region(0..4, 0..4, 1..4);
+
+        assert_eq!(layers.size(), rconfig.output_dimensions(&sliced));
+        assert_eq!(
+            surface_to_rgba(&layers),
+            surface_to_rgba(&rconfig.render_map(&sliced).unwrap())
+        );
+        assert!(layers.height() < rconfig.render_map(&iso_map).unwrap().height());
+    }
+
+    #[test]
+    fn debug_outlines() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(3);
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0[[1, 1, 0]] = Block::Rock;
+
+        let plain = rconfig.render_map(&iso_map).unwrap();
+        let debug = rconfig.render_debug(&iso_map).unwrap();
+        assert_eq!(plain.size(), debug.size());
+
+        let (_, _, placements) = rconfig
+            .layout_map(&iso_map, &mut StdRng::seed_from_u64(3))
+            .unwrap();
+        let dest = placements[0].1;
+        let pixel = |surf: &Surface, x: i32, y: i32| {
+            let start = (y as usize * surf.width() as usize + x as usize) * 4;
+            surface_to_rgba(surf)[start..start + 4].to_vec()
+        };
+
+        assert_eq!(pixel(&debug, dest.x(), dest.y()), vec![255, 0, 255, 255]);
+        assert_eq!(
+            pixel(&debug, dest.right() - 1, dest.bottom() - 1),
+            vec![255, 0, 255, 255]
+        );
+        assert_eq!(
+            pixel(&debug, dest.x() + 1, dest.y() + 1),
+            pixel(&plain, dest.x() + 1, dest.y() + 1)
+        );
+    }
+
+    #[test]
+    fn render_into_existing_surface() {
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(3);
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0.fill(Block::Rock);
+
+        let rendered = rconfig.render_map(&iso_map).unwrap();
+        let (width, height) = rconfig.output_dimensions(&iso_map);
+
+        // A dirty surface of the right size ends up identical to a fresh render
+        let mut target = Surface::new(width, height, rendered.pixel_format_enum()).unwrap();
+        target.fill_rect(None, Color::RGB(255, 0, 0)).unwrap();
+        rconfig.render_map_into(&iso_map, &mut target).unwrap();
+
+        assert_eq!(surface_to_rgba(&target), surface_to_rgba(&rendered));
+    }
+
+    #[test]
+    fn render_into_too_small() {
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+        let iso_map = IsoMap::new_empty(4);
+        let (width, height) = rconfig.output_dimensions(&iso_map);
+
+        let mut target = Surface::new(width, height - 1, PixelFormatEnum::RGB24).unwrap();
+
+        match rconfig.render_map_into(&iso_map, &mut target) {
+            Err(RendererError::TargetTooSmall { target_height, .. }) => {
+                assert_eq!(target_height, height - 1)
+            }
+            _ => panic!("expected TargetTooSmall"),
+        }
+    }
+
+    #[test]
+    fn translucent_blocks_dont_occlude() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.fill(Block::Rock);
+
+        assert!(is_occluded(&iso_map.0.view(), 1, 1, 1, &[Block::Water]));
+
+        iso_map.0[[1, 1, 2]] = Block::Water;
+        assert!(!is_occluded(&iso_map.0.view(), 1, 1, 1, &[Block::Water]));
+        assert!(is_occluded(&iso_map.0.view(), 1, 1, 1, &[]));
+    }
+
+    #[test]
+    fn floor_order_is_back_to_front() {
+        for &(len_x, len_y) in &[(1, 1), (4, 4), (3, 7), (7, 3), (0, 5)] {
+            let order: Vec<(usize, usize)> = floor_order(len_x, len_y).collect();
+            let position = |pos| order.iter().position(|&p| p == pos).unwrap();
+
+            assert_eq!(order.len(), len_x * len_y);
+
+            for x in 0..len_x {
+                for y in 0..len_y {
+                    if x + 1 < len_x {
+                        assert!(position((x, y)) < position((x + 1, y)));
+                    }
+                    if y + 1 < len_y {
+                        assert!(position((x, y)) < position((x, y + 1)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn checkerboard_independent_of_layout() {
+        use ndarray::{Array3, ShapeBuilder};
+
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .with_seed(9);
+
+        // Alternating tall and short columns, where any tile drawn before the
+        // one behind it would be visibly overlapped
+        let dims = (6, 6, 4);
+        let checkerboard = |(x, y, z): (usize, usize, usize)| {
+            let height = if (x + y) % 2 == 0 { 4 } else { 1 };
+            if z < height {
+                Block::Rock
+            } else {
+                Block::Air
+            }
+        };
+
+        let standard = IsoMap(Array3::from_shape_fn(dims, checkerboard));
+        let fortran = IsoMap(Array3::from_shape_fn(dims.f(), checkerboard));
+
+        let standard = rconfig.render_map(&standard).unwrap();
+        let fortran = rconfig.render_map(&fortran).unwrap();
+
+        assert_eq!(surface_to_rgba(&standard), surface_to_rgba(&fortran));
+    }
+
+    #[test]
+    fn facings_turn_in_order() {
+        let mut iso_map = IsoMap::new_empty_dims(3, 2, 1);
+        iso_map.0[[2, 0, 0]] = Block::Rock;
+        iso_map.0[[0, 1, 0]] = Block::Soil;
+
+        let turn = |map: &IsoMap, facing| IsoMap(oriented(&map.0, facing).to_owned());
+
+        assert_eq!(turn(&iso_map, Facing::North).0, iso_map.0);
+        assert_eq!(turn(&iso_map, Facing::East).0.shape(), &[2, 3, 1]);
+
+        let east = turn(&iso_map, Facing::East);
+        assert_eq!(turn(&east, Facing::East).0, turn(&iso_map, Facing::South).0);
+        assert_eq!(turn(&east, Facing::South).0, turn(&iso_map, Facing::West).0);
+        assert_eq!(turn(&east, Facing::West).0, iso_map.0);
+    }
+
+    #[test]
+    fn facing_renders_turned_map() {
+        use map::generator::{Generator, TerGenTwo};
+
+        let load = || {
+            Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .with_seed(2)
+        };
+        let iso_map = TerGenTwo::new()
+            .set_dims(8, 5, 6)
+            .set_min_soil_cutoff(2)
+            .set_seed(2)
+            .generate();
+
+        let turned = IsoMap(oriented(&iso_map.0, Facing::West).to_owned());
+
+        let facing_west = load().set_facing(Facing::West);
+        assert_eq!(
+            surface_to_rgba(&facing_west.render_map(&iso_map).unwrap()),
+            surface_to_rgba(&load().render_map(&turned).unwrap())
+        );
+    }
+
+    #[test]
+    fn scaled_render() {
+        let load = || {
+            Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .with_seed(4)
+        };
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[1, 1, 1]] = Block::Water;
+
+        let unscaled = load().render_map(&iso_map).unwrap();
+        let scaled = load().set_scale(3).render_map(&iso_map).unwrap();
+
+        assert_eq!(load().set_scale(1).render_map(&iso_map).unwrap().size(), unscaled.size());
+        assert_eq!(scaled.width(), unscaled.width() * 3);
+        assert_eq!(scaled.height(), unscaled.height() * 3);
+
+        // Every pixel becomes a 3×3 block of the same color
+        let small = surface_to_rgba(&unscaled);
+        let large = surface_to_rgba(&scaled);
+        let pixel = |buffer: &[u8], width: u32, x: u32, y: u32| {
+            let i = ((y * width + x) * 4) as usize;
+            buffer[i..i + 4].to_vec()
+        };
+
+        for y in 0..scaled.height() {
+            for x in 0..scaled.width() {
+                assert_eq!(
+                    pixel(&large, scaled.width(), x, y),
+                    pixel(&small, unscaled.width(), x / 3, y / 3)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn depth_brightness() {
+        let rconfig = test_renderer(HashMap::new());
+        assert_eq!(rconfig.brightness(0, 5), 255);
+
+        let rconfig = rconfig.set_depth_shading(1.0);
+        assert_eq!(rconfig.brightness(0, 5), 0);
+        assert_eq!(rconfig.brightness(2, 5), 128);
+        assert_eq!(rconfig.brightness(4, 5), 255);
+        assert_eq!(rconfig.brightness(0, 1), 255);
+
+        let rconfig = rconfig.set_depth_shading(0.5);
+        assert_eq!(rconfig.brightness(0, 5), 128);
+    }
+
+    #[test]
+    fn ambient_occlusion() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[0, 1, 1]] = Block::Rock;
+        iso_map.0[[1, 0, 1]] = Block::Rock;
+        iso_map.0[[0, 0, 1]] = Block::Water;
+        let map = iso_map.0.view();
+
+        // Water isn't solid, so it doesn't count
+        assert_eq!(occluding_neighbours(&map, 1, 1, 0), 2);
+        assert_eq!(occluding_neighbours(&map, 0, 1, 0), 0);
+        assert_eq!(occluding_neighbours(&map, 2, 1, 0), 1);
+        assert_eq!(occluding_neighbours(&map, 1, 1, 2), 0);
+
+        iso_map.0[[0, 0, 1]] = Block::Rock;
+        let map = iso_map.0.view();
+        assert_eq!(occluding_neighbours(&map, 1, 1, 0), 3);
+
+        let rconfig = test_renderer(HashMap::new());
+        assert_eq!(rconfig.shaded_brightness(&map, 1, 1, 0), 255);
+
+        let rconfig = rconfig.set_ao(0.6);
+        assert_eq!(rconfig.shaded_brightness(&map, 1, 1, 0), 102);
+        assert_eq!(rconfig.shaded_brightness(&map, 2, 1, 0), 204);
+        assert_eq!(rconfig.shaded_brightness(&map, 2, 2, 0), 255);
+    }
+
+    #[test]
+    fn ambient_occlusion_darkens() {
+        let mut iso_map = IsoMap::new_empty(3);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+        iso_map.0[[0, 1, 1]] = Block::Rock;
+        iso_map.0[[1, 0, 1]] = Block::Rock;
+        iso_map.0[[0, 0, 1]] = Block::Rock;
+
+        let rconfig = solid_renderer().set_ao(0.6);
+        let (_, _, placements) = rconfig
+            .layout_map(&iso_map, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+
+        // The block in the corner is the darkest one, and the middle of its
+        // top face can be seen
+        let dest = placements
+            .iter()
+            .min_by_key(|placement| placement.2)
+            .unwrap()
+            .1;
+        let (width, _) = rconfig.output_dimensions(&iso_map);
+        let pixel = |rendered: &[u8]| {
+            let start = ((dest.y() + 6) as usize * width as usize + (dest.x() + 12) as usize) * 4;
+            rendered[start..start + 3].to_vec()
+        };
+
+        let plain = surface_to_rgba(&solid_renderer().render_map(&iso_map).unwrap());
+        let occluded = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+
+        let (plain, occluded) = (pixel(&plain), pixel(&occluded));
+        assert!(plain.iter().zip(&occluded).all(|(plain, occluded)| occluded < plain));
+    }
+
+    #[test]
+    fn depth_shading_darkens() {
+        let load = || {
+            Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .with_seed(1)
+        };
+
+        let mut iso_map = IsoMap::new_empty(4);
+        iso_map.0.slice_mut(s![.., .., 0]).fill(Block::Rock);
+
+        let plain = surface_to_rgba(&load().render_map(&iso_map).unwrap());
+        let shaded_renderer = load().set_depth_shading(1.0);
+        let shaded = surface_to_rgba(&shaded_renderer.render_map(&iso_map).unwrap());
+        let background = DEFAULT_BACKGROUND_COLOR!();
+
+        // Every pixel of the bottom floor is black, and the background stays
+        let mut saw_tile = false;
+        for (plain, shaded) in plain.chunks(4).zip(shaded.chunks(4)) {
+            if plain[..3] == [background.r, background.g, background.b] {
+                assert_eq!(plain, shaded);
+            } else {
+                saw_tile = true;
+                assert_eq!(&shaded[..3], &[0, 0, 0]);
+            }
+        }
+        assert!(saw_tile);
+
+        // The sheets themselves weren't changed
+        let again = surface_to_rgba(&load().render_map(&iso_map).unwrap());
+        assert_eq!(plain, again);
+        for tile in &shaded_renderer.tiles[&Block::Rock] {
+            assert_eq!(tile.sheet.color_mod(), Color::RGB(255, 255, 255));
+        }
+    }
+
+    #[test]
+    fn transparent_edges_blend() {
+        use std::env;
+
+        // A red tile with a half transparent border
+        let mut sheet = Surface::new(24, 26, PixelFormatEnum::RGBA32).unwrap();
+        sheet.fill_rect(None, Color::RGBA(255, 0, 0, 128)).unwrap();
+        sheet
+            .fill_rect(Rect::new(1, 1, 22, 24), Color::RGBA(255, 0, 0, 255))
+            .unwrap();
+
+        let mut base = env::temp_dir();
+        base.push(format!("cubeglobe-alpha-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        sheet.save(base.join("alpha.png")).unwrap();
+
+        let tiles: Vec<String> = Block::into_enum_iter()
+            .filter(|&block| block != Block::Air)
+            .map(|block| format!("{{ kind = \"{:?}\" }}", block))
+            .collect();
+        let config = format!(
+            "width = 24\nheight = 26\n[[files]]\nfilename = \"alpha.png\"\ntiles = [{}]\n",
+            tiles.join(", ")
+        );
+
+        let rconfig = Renderer::from_config_str_with_base(&config, &base)
+            .unwrap()
+            .set_background(Color::RGB(0, 0, 255));
+        fs::remove_dir_all(&base).unwrap();
+
+        let mut iso_map = IsoMap::new_empty(1);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+
+        let rendered = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        let (width, _) = rconfig.output_dimensions(&iso_map);
+        let (_, _, placements) = rconfig
+            .layout_map(&iso_map, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        let dest = placements[0].1;
+        let pixel = |x: i32, y: i32| {
+            let start = (y as usize * width as usize + x as usize) * 4;
+            rendered[start..start + 4].to_vec()
+        };
+
+        // The background shows through the border, but not the middle
+        let border = pixel(dest.x(), dest.y());
+        assert!(border[0] > 100 && border[0] < 160);
+        assert!(border[2] > 100 && border[2] < 160);
+        assert_eq!(pixel(dest.x() + 2, dest.y() + 2), vec![255, 0, 0, 255]);
+    }
+
+    /// Load a renderer with a plain white tile for every block, where rock
+    /// has `faces` set to the TOML in `faces`
+    fn white_tiles_with_faces(faces: &str) -> Result<Renderer<'static>, ConfigLoadError> {
+        use std::env;
+
+        let mut sheet = Surface::new(24, 26, PixelFormatEnum::RGBA32).unwrap();
+        sheet.fill_rect(None, Color::RGB(255, 255, 255)).unwrap();
+
+        let mut base = env::temp_dir();
+        base.push(format!("cubeglobe-faces-{}", std::process::id()));
+        fs::create_dir_all(&base).unwrap();
+        sheet.save(base.join("white.png")).unwrap();
+
+        let tiles: String = Block::into_enum_iter()
+            .filter(|&block| block != Block::Air && block != Block::Rock)
+            .map(|block| format!("[[files.tiles]]\nkind = \"{:?}\"\n", block))
+            .collect();
+        let config = format!(
+            "width = 24\nheight = 26\n[[files]]\nfilename = \"white.png\"\n{}\
+             [[files.tiles]]\nkind = \"Rock\"\n[files.tiles.faces]\n{}\n",
+            tiles, faces
+        );
+
+        let rconfig = Renderer::from_config_str_with_base(&config, &base);
+        fs::remove_dir_all(&base).unwrap();
+
+        rconfig
+    }
+
+    #[test]
+    fn faces_are_lit_separately() {
+        let rconfig = white_tiles_with_faces(
+            "top = { x = 0, y = 0, width = 24, height = 12 }\n\
+             left = { x = 0, y = 12, width = 12, height = 14 }\n\
+             right = { x = 12, y = 12, width = 12, height = 14 }",
+        ).unwrap();
+
+        let mut iso_map = IsoMap::new_empty(1);
+        iso_map.0[[0, 0, 0]] = Block::Rock;
+
+        let (_, _, placements) = rconfig
+            .layout_map(&iso_map, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        let dest = placements[0].1;
+        let (width, _) = rconfig.output_dimensions(&iso_map);
+        let pixel = |rendered: &[u8], x: i32, y: i32| {
+            let start = ((dest.y() + y) as usize * width as usize + (dest.x() + x) as usize) * 4;
+            rendered[start..start + 3].to_vec()
+        };
+
+        // Faces covering the whole tile look the same as the tile unlit
+        let unlit = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        assert_eq!(pixel(&unlit, 20, 20), vec![255, 255, 255]);
+
+        let rconfig = rconfig.set_light(1.0, 0.5, 0.0);
+        let lit = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        assert_eq!(pixel(&lit, 12, 6), vec![255, 255, 255]);
+        assert_eq!(pixel(&lit, 4, 20), vec![128, 128, 128]);
+        assert_eq!(pixel(&lit, 20, 20), vec![0, 0, 0]);
+
+        // Faces are scaled along with the tile
+        let rconfig = rconfig.set_scale(2);
+        let scaled = surface_to_rgba(&rconfig.render_map(&iso_map).unwrap());
+        let (_, _, placements) = rconfig
+            .layout_map(&iso_map, &mut StdRng::seed_from_u64(0))
+            .unwrap();
+        let dest = placements[0].1;
+        let width = rconfig.output_dimensions(&iso_map).0 as usize;
+        let start = ((dest.y() + 48) as usize * width + (dest.x() + 40) as usize) * 4;
+        assert_eq!(&scaled[start..start + 3], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn face_out_of_bounds() {
+        let faces = "top = { x = 0, y = 0, width = 24, height = 12 }\n\
+                     left = { x = 0, y = 12, width = 12, height = 14 }\n\
+                     right = { x = 12, y = 12, width = 13, height = 14 }";
+
+        match white_tiles_with_faces(faces) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::FaceOutOfBounds(Block::Rock)),
+            Ok(_) => panic!(),
         }
     }
+
+    #[test]
+    fn png_output() {
+        use map::generator::{Generator, TestingGenerator};
+        use std::env;
+
+        let rconfig = Renderer::from_config_file(&test_config_path()).unwrap();
+        let iso_map = TestingGenerator { dim: 8 }.generate();
+
+        let mut path = env::temp_dir();
+        path.push("cubeglobe-png-output.png");
+        rconfig.render_map_to_png(&iso_map, &path).unwrap();
+
+        let rendered = rconfig.render_map(&iso_map).unwrap();
+        let loaded = Surface::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.size(), rendered.size());
+        assert_eq!(loaded.pixel_format_enum(), PixelFormatEnum::RGB24);
+    }
+
+    #[test]
+    fn buffer_output() {
+        let mut surf = Surface::new(3, 2, PixelFormatEnum::RGB24).unwrap();
+        surf.fill_rect(None, Color::RGB(10, 20, 30)).unwrap();
+        surf.fill_rect(Rect::new(2, 1, 1, 1), Color::RGB(40, 50, 60)).unwrap();
+
+        let buffer = surface_to_rgba(&surf);
+
+        assert_eq!(buffer.len(), 3 * 2 * 4);
+        assert_eq!(&buffer[0..4], &[10, 20, 30, 255]);
+        assert_eq!(&buffer[20..24], &[40, 50, 60, 255]);
+    }
+
+    #[test]
+    fn transparent_background() {
+        use map::generator::{Generator, TestingGenerator};
+
+        let rconfig = Renderer::from_config_file(&test_config_path())
+            .unwrap()
+            .set_background(Color::RGBA(0, 0, 0, 0));
+        let iso_map = TestingGenerator { dim: 8 }.generate();
+
+        let (buffer, _, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+
+        // The top left corner is always part of the margin
+        assert_eq!(&buffer[0..4], &[0, 0, 0, 0]);
+    }
 }
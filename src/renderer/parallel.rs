@@ -0,0 +1,210 @@
+//! Multithreaded rendering, available with the `rayon` feature
+//!
+//! SDL surfaces can't be shared between threads, so the spritesheets are
+//! copied out into plain memory, and every thread makes its own surfaces out of
+//! them. The output is split into horizontal bands, each of which is rendered
+//! by blitting every tile which overlaps it, in the same order as the serial
+//! renderer does. Since every pixel sees the same sequence of blits, the result
+//! is identical to [`render_map`](../struct.Renderer.html#method.render_map).
+
+use rayon::prelude::*;
+use sdl2::render::BlendMode;
+
+use super::*;
+
+/// Number of rows of the output rendered by a single task, before scaling
+const BAND_HEIGHT: u32 = 64;
+
+/// The pixels of a spritesheet, copied out of its surface
+struct Sheet {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    pitch: u32,
+    format: PixelFormatEnum,
+    blend_mode: BlendMode,
+    color_key: Option<Color>,
+}
+
+impl Sheet {
+    fn from_surface(surf: &Surface) -> Result<Sheet, String> {
+        // Indexed surfaces would also need their palette copied, so we
+        // sidestep that by converting them
+        let converted = match surf.pixel_format_enum() {
+            PixelFormatEnum::Index1LSB
+            | PixelFormatEnum::Index1MSB
+            | PixelFormatEnum::Index4LSB
+            | PixelFormatEnum::Index4MSB
+            | PixelFormatEnum::Index8 => Some(surf.convert_format(PixelFormatEnum::RGBA32)?),
+            _ => None,
+        };
+        let surf = converted.as_ref().unwrap_or(surf);
+
+        Ok(Sheet {
+            pixels: surf.with_lock(|p| p.to_vec()),
+            width: surf.width(),
+            height: surf.height(),
+            pitch: surf.pitch(),
+            format: surf.pixel_format_enum(),
+            blend_mode: surf.blend_mode(),
+            color_key: surf.color_key().ok(),
+        })
+    }
+}
+
+impl<'a> Renderer<'a> {
+    /// Render an `IsoMap` using multiple threads
+    ///
+    /// The output is identical to [`render_map`](#method.render_map), but
+    /// large maps render considerably faster.
+    pub fn render_map_parallel<'b>(&self, isomap: &IsoMap) -> Result<Surface<'b>, RendererError> {
+        match self.seed {
+            Some(seed) => {
+                self.render_map_parallel_with_rng(isomap, &mut StdRng::seed_from_u64(seed))
+            }
+            None => self.render_map_parallel_with_rng(isomap, &mut rand::thread_rng()),
+        }
+    }
+
+    fn render_map_parallel_with_rng<'b, R: Rng>(
+        &self,
+        isomap: &IsoMap,
+        rng: &mut R,
+    ) -> Result<Surface<'b>, RendererError> {
+        let (surf_width, surf_height, placements) = self.layout_map(isomap, rng)?;
+
+        // Copy out every sheet that's used, and refer to them by index
+        let mut sheet_surfs: Vec<&Rc<Surface>> = Vec::new();
+        let mut blits: Vec<(usize, Rect, Rect, u8)> = Vec::with_capacity(placements.len());
+
+        for (tile, dest, brightness, _, _) in placements {
+            let index = match sheet_surfs.iter().position(|s| Rc::ptr_eq(s, &tile.sheet)) {
+                Some(index) => index,
+                None => {
+                    sheet_surfs.push(&tile.sheet);
+                    sheet_surfs.len() - 1
+                }
+            };
+
+            blits.extend(
+                tile.parts(0, dest, brightness, self.light)
+                    .into_iter()
+                    .map(|(src, dest, brightness)| (index, src, dest, brightness)),
+            );
+        }
+
+        let sheets = sheet_surfs
+            .iter()
+            .map(|s| Sheet::from_surface(s))
+            .collect::<Result<Vec<Sheet>, String>>()?;
+
+        let mut out = self.new_output_surface(surf_width, surf_height)?;
+        let format = out.pixel_format_enum();
+        let background = self.background;
+        let row_len = (surf_width * format.byte_size_per_pixel() as u32) as usize;
+
+        // Tiles are placed at multiples of the scale, so bands which also
+        // start at multiples of it clip scaled tiles at whole source pixels
+        let band_height = BAND_HEIGHT * self.scale;
+        let band_tops: Vec<u32> = (0..surf_height).step_by(band_height as usize).collect();
+
+        let bands = band_tops
+            .into_par_iter()
+            .map(|band_top| -> Result<Vec<u8>, String> {
+                let top = band_top as i32;
+                let bottom = (band_top + band_height).min(surf_height) as i32;
+
+                let mut band_surf = Surface::new(surf_width, (bottom - top) as u32, format)?;
+                band_surf.fill_rect(None, background)?;
+
+                let mut sheet_pixels: Vec<Vec<u8>> =
+                    sheets.iter().map(|s| s.pixels.clone()).collect();
+                let mut sheet_surfs = sheet_pixels
+                    .iter_mut()
+                    .zip(&sheets)
+                    .map(|(pixels, s)| {
+                        let mut surf =
+                            Surface::from_data(pixels, s.width, s.height, s.pitch, s.format)?;
+                        surf.set_blend_mode(s.blend_mode)?;
+                        if let Some(key) = s.color_key {
+                            surf.set_color_key(true, key)?;
+                        }
+                        Ok(surf)
+                    })
+                    .collect::<Result<Vec<Surface>, String>>()?;
+
+                for &(index, src, dest, brightness) in &blits {
+                    if dest.bottom() <= top || dest.top() >= bottom {
+                        continue;
+                    }
+
+                    // Every thread has its own surfaces, so their color mod
+                    // can be changed freely
+                    let color_mod = Color::RGB(brightness, brightness, brightness);
+                    sheet_surfs[index].set_color_mod(color_mod);
+
+                    let band_dest =
+                        Rect::new(dest.x(), dest.y() - top, dest.width(), dest.height());
+                    blit_tile(&sheet_surfs[index], src, &mut band_surf, band_dest)?;
+                }
+
+                let pitch = band_surf.pitch() as usize;
+                Ok(band_surf.with_lock(|pixels| {
+                    pixels
+                        .chunks(pitch)
+                        .flat_map(|row| row[..row_len].iter().cloned())
+                        .collect()
+                }))
+            })
+            .collect::<Result<Vec<Vec<u8>>, String>>()?;
+
+        let pitch = out.pitch() as usize;
+        out.with_lock_mut(|pixels| {
+            let rows = bands.iter().flat_map(|band| band.chunks(row_len));
+
+            for (out_row, row) in pixels.chunks_mut(pitch).zip(rows) {
+                out_row[..row_len].copy_from_slice(row);
+            }
+        });
+
+        if self.axes {
+            self.draw_axes(isomap, &mut out)?;
+        }
+
+        if self.legend {
+            self.draw_legend(isomap, &mut out)?;
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::tests::test_config_path;
+    use map::generator::{Generator, TerGenTwo};
+
+    #[test]
+    fn parallel_matches_serial() {
+        let load = || {
+            Renderer::from_config_file(&test_config_path())
+                .unwrap()
+                .with_seed(5)
+        };
+        let iso_map = TerGenTwo::new()
+            .set_len(24)
+            .set_min_soil_cutoff(10)
+            .set_seed(5)
+            .generate();
+
+        // Ambient occlusion changes the brightness of single blocks
+        for rconfig in &[load(), load().set_ao(0.5)] {
+            let serial = rconfig.render_map(&iso_map).unwrap();
+            let parallel = rconfig.render_map_parallel(&iso_map).unwrap();
+
+            assert_eq!(serial.size(), parallel.size());
+            assert_eq!(surface_to_rgba(&serial), surface_to_rgba(&parallel));
+        }
+    }
+}
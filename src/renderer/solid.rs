@@ -0,0 +1,162 @@
+//! Renderer which draws solid colored cubes instead of tiles loaded from images
+
+use super::*;
+
+impl<'a> Renderer<'a> {
+    /// Create a new renderer which draws every block as a cube of a single
+    /// color, without needing any tile images
+    ///
+    /// `width` and `height` are the dimensions of a tile in pixels, as in the
+    /// TOML configuration, so the width has to be a multiple of 4. `colors`
+    /// must have a color for every block other than
+    /// [`Block::Air`](../map/enum.Block.html#variant.Air). The top face of
+    /// each cube is drawn in the given color, while the sides are shaded darker,
+    /// so that the cubes line up exactly where tiles of the same size would.
+    ///
+    /// [`Block::default_color`](../map/enum.Block.html#method.default_color)
+    /// gives a color for every block, which can be used for any blocks that
+    /// don't need a color of their own.
+    pub fn solid_colors(
+        width: u32,
+        height: u32,
+        colors: HashMap<Block, Color>,
+    ) -> Result<Self, ConfigLoadError> {
+        // All the cubes go into a single spritesheet, side by side
+        let mut sheet = Surface::new(width * colors.len() as u32, height, PixelFormatEnum::RGBA32)
+            .map_err(ConfigLoadErrorKind::from_sdl_string_err)?;
+
+        let pitch = sheet.pitch() as usize;
+        let mut positions: Vec<(Block, Rect)> = Vec::with_capacity(colors.len());
+
+        sheet.with_lock_mut(|pixels| {
+            for (i, (&block, &color)) in colors.iter().enumerate() {
+                let tile_x = i as u32 * width;
+                draw_cube(pixels, pitch, tile_x, width, height, color);
+                positions.push((block, Rect::new(tile_x as i32, 0, width, height)));
+            }
+        });
+
+        let sheet = Rc::new(sheet);
+        let mut tiles: HashMap<Block, Vec<Tile>> = HashMap::new();
+
+        for (block, pos) in positions {
+            tiles.entry(block).or_default().push(Tile {
+                sheet: Rc::clone(&sheet),
+                pos,
+                weight: 1,
+                frames: 1,
+                stride: width as i32,
+                autotile: None,
+                faces: None,
+                top_offset: 0,
+            });
+        }
+
+        Self::from_tiles(width, height, tiles)
+    }
+}
+
+/// Draw a cube in `color` into an RGBA32 buffer, with the left edge of the
+/// tile at `tile_x`
+///
+/// The shape follows the same 2:1 projection the renderer assumes for tiles:
+/// the top face is a diamond `width` wide and `width / 2` tall, and the sides
+/// take up the rest of `height` below it.
+fn draw_cube(pixels: &mut [u8], pitch: usize, tile_x: u32, width: u32, height: u32, color: Color) {
+    let half_width = width as f64 / 2.0;
+    let top_height = (width / 2) as f64;
+    let sides_height = height as f64 - top_height;
+
+    let shade = |factor: f64| {
+        Color::RGBA(
+            (color.r as f64 * factor) as u8,
+            (color.g as f64 * factor) as u8,
+            (color.b as f64 * factor) as u8,
+            color.a,
+        )
+    };
+    let left = shade(0.8);
+    let right = shade(0.6);
+
+    for py in 0..height {
+        for px in 0..width {
+            // Work with the centers of pixels, so the shape is symmetrical
+            let cx = px as f64 + 0.5;
+            let cy = py as f64 + 0.5;
+
+            // How far we are from the middle of the tile, horizontally, with 0
+            // in the middle and 1 at the edges
+            let d = (cx - half_width).abs() / half_width;
+
+            // Top and bottom edges of the diamond of the top face
+            let top_edge = top_height / 2.0 * d;
+            let bottom_edge = top_height / 2.0 * (2.0 - d);
+
+            let pixel_color = if cy < top_edge {
+                continue;
+            } else if cy < bottom_edge {
+                color
+            } else if cy < bottom_edge + sides_height {
+                if cx < half_width {
+                    left
+                } else {
+                    right
+                }
+            } else {
+                continue;
+            };
+
+            let offset = py as usize * pitch + (tile_x + px) as usize * 4;
+            pixels[offset..offset + 4].copy_from_slice(&[
+                pixel_color.r,
+                pixel_color.g,
+                pixel_color.b,
+                pixel_color.a,
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solid_cube() {
+        let mut colors = HashMap::new();
+        colors.insert(Block::Rock, Color::RGB(100, 100, 100));
+        colors.insert(Block::Grass, Color::RGB(0, 200, 0));
+        colors.insert(Block::Soil, Color::RGB(120, 80, 40));
+        colors.insert(Block::Water, Color::RGB(0, 0, 200));
+        colors.insert(Block::Sand, Color::RGB(220, 200, 140));
+        colors.insert(Block::Snow, Color::RGB(240, 245, 255));
+
+        let rconfig = Renderer::solid_colors(24, 26, colors).unwrap();
+
+        let mut iso_map = IsoMap::new_empty(1);
+        iso_map.0[[0, 0, 0]] = Block::Grass;
+
+        let (buffer, width, _) = rconfig.render_map_to_buffer(&iso_map).unwrap();
+        let pixel = |x: u32, y: u32| {
+            let offset = ((y * width + x) * 4) as usize;
+            &buffer[offset..offset + 4]
+        };
+
+        // The single tile is drawn at 24, 40. Check the top face and both
+        // sides.
+        assert_eq!(pixel(36, 46), &[0, 200, 0, 255]);
+        assert_eq!(pixel(30, 59), &[0, 160, 0, 255]);
+        assert_eq!(pixel(42, 59), &[0, 120, 0, 255]);
+    }
+
+    #[test]
+    fn solid_missing_color() {
+        let mut colors = HashMap::new();
+        colors.insert(Block::Rock, Color::RGB(100, 100, 100));
+
+        match Renderer::solid_colors(24, 26, colors) {
+            Err(e) => assert_eq!(e.kind(), &ConfigLoadErrorKind::MissingBlock(Block::Grass)),
+            Ok(_) => panic!(),
+        }
+    }
+}